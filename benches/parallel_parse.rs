@@ -0,0 +1,73 @@
+//! Building the per-agent matchers one after another, against building them on the rayon pool
+//! as `run_tests` does, for a synthetic suite with dozens of user agents
+//!
+//! Run with `cargo bench --bench parallel_parse`. The parallel build can only be faster with more
+//! than one thread in the pool.
+use std::time::{Duration, Instant};
+
+use rayon::prelude::*;
+use robots_txt_tester::{run_tests, TestCaseDefinition};
+use texting_robots::Robot;
+
+const USER_AGENTS: usize = 60;
+const RULES: usize = 400;
+const CASES_PER_AGENT: usize = 20;
+const ROUNDS: u32 = 5;
+
+fn main() {
+    let user_agents: Vec<String> = (0..USER_AGENTS).map(|index| format!("bot{}", index)).collect();
+    let robots = synthetic_robots(&user_agents);
+    let cases: Vec<TestCaseDefinition> = user_agents.iter()
+        .flat_map(|user_agent| (0..CASES_PER_AGENT).map(move |index| TestCaseDefinition {
+            user_agent: user_agent.clone(),
+            url: format!("/path{}/page", index * 7),
+            expected_result: false,
+            expected_crawl_delay: None,
+            line_number: None,
+            name: None,
+            reason: None,
+        }))
+        .collect();
+
+    let serial = time(|| {
+        for user_agent in &user_agents {
+            std::hint::black_box(Robot::new(user_agent, robots.as_bytes()).expect("synthetic robots.txt parses"));
+        }
+    });
+    let parallel = time(|| {
+        user_agents.par_iter().for_each(|user_agent| {
+            std::hint::black_box(Robot::new(user_agent, robots.as_bytes()).expect("synthetic robots.txt parses"));
+        });
+    });
+    let suite = time(|| {
+        std::hint::black_box(run_tests(robots.as_bytes(), &cases));
+    });
+
+    println!(
+        "{} user agents, {} rules, {} test cases, {} threads, mean of {} rounds",
+        USER_AGENTS, RULES, cases.len(), rayon::current_num_threads(), ROUNDS
+    );
+    println!("serial build:   {:>9.1?}", serial);
+    println!("parallel build: {:>9.1?}", parallel);
+    println!("run_tests:      {:>9.1?}", suite);
+}
+
+fn time(f: impl Fn()) -> Duration {
+    let start = Instant::now();
+    for _ in 0..ROUNDS {
+        f();
+    }
+    start.elapsed() / ROUNDS
+}
+
+fn synthetic_robots(user_agents: &[String]) -> String {
+    let mut robots = String::new();
+    for (index, user_agent) in user_agents.iter().enumerate() {
+        robots.push_str(&format!("User-agent: {}\n", user_agent));
+        for rule in (index..RULES).step_by(USER_AGENTS) {
+            robots.push_str(&format!("Disallow: /path{}/*\n", rule));
+        }
+        robots.push('\n');
+    }
+    robots
+}
@@ -0,0 +1,50 @@
+//! Deciding a large URL list with a `RobotCache` against parsing the robots.txt for each URL
+//!
+//! Run with `cargo bench --bench robot_cache`.
+use std::time::Instant;
+
+use robots_txt_tester::{normalize_url, RobotCache};
+use texting_robots::Robot;
+
+const USER_AGENTS: [&str; 4] = ["googlebot", "bingbot", "duckduckbot", "otherbot"];
+const URLS: usize = 100_000;
+// Parsing for every URL is slow enough that a sample of the list is timed and scaled up
+const UNCACHED_URLS: usize = 2_000;
+
+fn main() {
+    let robots = synthetic_robots();
+    let urls: Vec<(&str, String)> = (0..URLS)
+        .map(|index| (USER_AGENTS[index % USER_AGENTS.len()], format!("/section{}/page{}?id={}", index % 50, index, index % 7)))
+        .collect();
+
+    let start = Instant::now();
+    let cache = RobotCache::new(robots.as_bytes());
+    let cached_allowed = urls.iter()
+        .filter(|(user_agent, url)| cache.allowed(user_agent, url).expect("synthetic robots.txt parses"))
+        .count();
+    let cached = start.elapsed();
+
+    let start = Instant::now();
+    let uncached_allowed = urls[..UNCACHED_URLS].iter()
+        .filter(|(user_agent, url)| Robot::new(user_agent, robots.as_bytes()).expect("synthetic robots.txt parses").allowed(&normalize_url(url)))
+        .count();
+    let uncached = start.elapsed().mul_f64(URLS as f64 / UNCACHED_URLS as f64);
+
+    println!("{} URLs, {} user agents, {} rules", URLS, USER_AGENTS.len(), robots.lines().count());
+    println!("cached:   {:>10.1?} ({} allowed)", cached, cached_allowed);
+    println!("uncached: {:>10.1?} (scaled from {} URLs, {} allowed)", uncached, UNCACHED_URLS, uncached_allowed);
+    println!("speedup:  {:>9.0}x", uncached.as_secs_f64() / cached.as_secs_f64());
+}
+
+fn synthetic_robots() -> String {
+    let mut robots = String::new();
+    for user_agent in USER_AGENTS.iter().take(3) {
+        robots.push_str(&format!("User-agent: {}\n", user_agent));
+        for section in 0..50 {
+            robots.push_str(&format!("Disallow: /section{}/*?id=3\nAllow: /section{}/page1$\n", section, section));
+        }
+        robots.push('\n');
+    }
+    robots.push_str("User-agent: *\nDisallow: /section1\n");
+    robots
+}
@@ -0,0 +1,107 @@
+//! Measuring how fast the matcher decides URLs against a robots.txt, to find rules that are
+//! slow to match
+use std::collections::{BTreeMap, HashMap};
+use std::error::Error;
+use std::time::{Duration, Instant};
+
+use texting_robots::Robot;
+
+use crate::input::normalize_rule_escapes;
+use crate::normalize_url;
+
+/// How fast the URLs were matched
+#[derive(Debug, Clone, PartialEq)]
+pub struct BenchResult {
+    /// number of URLs matched while measuring
+    pub matches: u64,
+    /// time spent matching, leaving out the time spent keeping track of the latencies
+    pub elapsed: Duration,
+    pub p50: Duration,
+    pub p99: Duration,
+    /// index of the URL that took longest to match on average, if more than one was matched
+    pub slowest: Option<usize>,
+}
+
+impl BenchResult {
+    pub fn matches_per_second(&self) -> f64 {
+        self.matches as f64 / self.elapsed.as_secs_f64().max(f64::MIN_POSITIVE)
+    }
+}
+
+/// Match each `(user_agent, url)` in turn, over and over, for `warmup` and then for `duration`
+/// while timing each match
+///
+/// Parsing the robots.txt for each user agent is done up front and not measured. Fails if it
+/// can't be parsed for one of them.
+pub fn bench(robots: &[u8], targets: &[(&str, &str)], warmup: Duration, duration: Duration) -> Result<BenchResult, Box<dyn Error>> {
+    let rules = normalize_rule_escapes(robots);
+    let mut robots_by_agent = HashMap::new();
+    for (user_agent, _) in targets {
+        if !robots_by_agent.contains_key(user_agent) {
+            let r = Robot::new(user_agent, &rules)
+                .map_err(|e| format!("unable to parse robots.txt for user agent {}: {:#}", user_agent, e))?;
+            robots_by_agent.insert(*user_agent, r);
+        }
+    }
+    let targets: Vec<(&Robot, String)> = targets.iter()
+        .map(|(user_agent, url)| (&robots_by_agent[user_agent], normalize_url(url).into_owned()))
+        .collect();
+    if targets.is_empty() {
+        return Err("there are no URLs to match".into());
+    }
+
+    let warmup_start = Instant::now();
+    for (r, url) in targets.iter().cycle() {
+        std::hint::black_box(r.allowed(url));
+        if warmup_start.elapsed() >= warmup {
+            break;
+        }
+    }
+
+    // Counted by latency in nanoseconds, as the clock only has so many distinct readings a
+    // match could take, where keeping every latency would grow with the duration
+    let mut latencies: BTreeMap<u64, u64> = BTreeMap::new();
+    let mut totals = vec![Duration::ZERO; targets.len()];
+    let mut counts = vec![0u64; targets.len()];
+    let mut matches = 0;
+    let start = Instant::now();
+    for (index, (r, url)) in targets.iter().enumerate().cycle() {
+        let match_start = Instant::now();
+        std::hint::black_box(r.allowed(url));
+        let latency = match_start.elapsed();
+
+        *latencies.entry(u64::try_from(latency.as_nanos()).unwrap_or(u64::MAX)).or_default() += 1;
+        totals[index] += latency;
+        counts[index] += 1;
+        matches += 1;
+        if start.elapsed() >= duration {
+            break;
+        }
+    }
+
+    let slowest = (targets.len() > 1).then(|| {
+        (0..targets.len())
+            .max_by_key(|&index| totals[index].as_nanos() / u128::from(counts[index].max(1)))
+            .unwrap_or_default()
+    });
+    Ok(BenchResult {
+        matches,
+        elapsed: totals.iter().sum(),
+        p50: percentile(&latencies, matches, 50),
+        p99: percentile(&latencies, matches, 99),
+        slowest,
+    })
+}
+
+/// The latency at or below which `percent` of the `total` matches took
+fn percentile(latencies: &BTreeMap<u64, u64>, total: u64, percent: u64) -> Duration {
+    let rank = (total * percent).div_ceil(100).max(1);
+    let mut seen = 0;
+    for (&nanos, &count) in latencies {
+        seen += count;
+        if seen >= rank {
+            return Duration::from_nanos(nanos);
+        }
+    }
+    Duration::ZERO
+}
@@ -0,0 +1,77 @@
+//! Keeping fetched robots.txt files on disk, to test against a live site without fetching it
+//! on every run
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// A directory of fetched robots.txt files, each kept until it expires
+///
+/// Each file is named after a hash of its URL and starts with a line holding the time it expires
+/// and the URL, followed by the robots.txt as it was fetched.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FetchCache {
+    dir: PathBuf,
+    /// how long a robots.txt is kept when the server doesn't say
+    ttl: Duration,
+}
+
+impl FetchCache {
+    pub fn new(dir: impl Into<PathBuf>, ttl: Duration) -> FetchCache {
+        FetchCache { dir: dir.into(), ttl }
+    }
+
+    /// Path of the file caching `url`
+    pub fn path(&self, url: &str) -> PathBuf {
+        self.dir.join(format!("{:016x}.robots.txt", fnv1a(url.as_bytes())))
+    }
+
+    /// The cached robots.txt for `url`, if there is one that hasn't expired yet
+    pub fn get(&self, url: &str) -> io::Result<Option<Vec<u8>>> {
+        let content = match fs::read(self.path(url)) {
+            Ok(content) => content,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e),
+        };
+        let Some(header_end) = content.iter().position(|&byte| byte == b'\n') else { return Ok(None) };
+        let header = String::from_utf8_lossy(&content[..header_end]);
+        let Some((expires, cached_url)) = header.split_once(' ') else { return Ok(None) };
+
+        // Another URL with the same hash, or a damaged file, is fetched again and overwritten
+        match expires.parse::<u64>() {
+            Ok(expires) if cached_url == url && unix_time() < expires => Ok(Some(content[header_end + 1..].to_vec())),
+            _ => Ok(None),
+        }
+    }
+
+    /// Cache `content` for `url`, for `max_age` when the server gave one and the TTL otherwise
+    ///
+    /// Nothing is cached for a zero max age, such as from `Cache-Control: no-store`.
+    pub fn put(&self, url: &str, content: &[u8], max_age: Option<Duration>) -> io::Result<()> {
+        let max_age = max_age.unwrap_or(self.ttl);
+        if max_age.is_zero() || url.contains('\n') {
+            return Ok(());
+        }
+        fs::create_dir_all(&self.dir)?;
+
+        let mut cached = format!("{} {}\n", unix_time().saturating_add(max_age.as_secs()), url).into_bytes();
+        cached.extend_from_slice(content);
+        // Written next to the cache file first, so a run reading it never sees half of it
+        let path = self.path(url);
+        let partial_path = path.with_extension("partial");
+        fs::write(&partial_path, cached)?;
+        fs::rename(&partial_path, &path)
+    }
+}
+
+fn unix_time() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |elapsed| elapsed.as_secs())
+}
+
+/// 64-bit FNV-1a, which unlike the standard library's hasher is the same on every platform and
+/// release
+pub(crate) fn fnv1a(bytes: &[u8]) -> u64 {
+    const OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+    const PRIME: u64 = 0x0000_0100_0000_01b3;
+    bytes.iter().fold(OFFSET_BASIS, |hash, &byte| (hash ^ u64::from(byte)).wrapping_mul(PRIME))
+}
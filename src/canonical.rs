@@ -0,0 +1,146 @@
+//! A canonical form of a robots.txt's rules, to tell a change to what it allows from a cosmetic
+//! edit
+//!
+//! Two robots.txt files with the same canonical form are matched the same way. It is built by:
+//!
+//! - dropping comments, blank lines, lines without a `key: value` separator and unknown
+//!   directives, none of which the matcher uses
+//! - lowercasing directive keys and user agents, as the matcher ignores their case
+//! - normalising the percent-encoding of `Allow` and `Disallow` patterns, see
+//!   [`normalize_percent_encoding`]
+//! - sorting and deduplicating the user agents of each group and the rules within it, as the
+//!   longest matching rule wins wherever it is
+//! - keeping only the first `Crawl-delay` line of each group, as later ones are ignored
+//! - merging groups naming the same user agents, and sorting the groups
+//! - sorting and deduplicating `Sitemap` lines, and keeping only the first `Host` line as later
+//!   ones are ignored
+//!
+//! Rules before the first `User-agent` line are kept as a group without user agents.
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::cache::fnv1a;
+use crate::directives::{parse_lines, DirectiveKind, ParsedLine};
+use crate::input::normalize_percent_encoding;
+
+/// The canonical form of the robots.txt's rules, one directive per line with a blank line
+/// between groups
+pub fn canonical_rules(robots: &str) -> String {
+    let mut groups: BTreeMap<BTreeSet<String>, Group> = BTreeMap::new();
+    let mut sitemaps = BTreeSet::new();
+    let mut host = None;
+
+    let mut user_agents = BTreeSet::new();
+    let mut group = Group::default();
+    let mut in_user_agents = false;
+    for line in parse_lines(robots) {
+        let ParsedLine::Directive(line) = line else { continue };
+        match line.kind() {
+            Some(DirectiveKind::UserAgent) => {
+                // Consecutive User-agent lines share the group that follows them
+                if !in_user_agents {
+                    groups.entry(std::mem::take(&mut user_agents)).or_default().merge(std::mem::take(&mut group));
+                }
+                in_user_agents = true;
+                user_agents.insert(line.value.to_lowercase());
+            },
+            Some(kind @ (DirectiveKind::Allow | DirectiveKind::Disallow)) => {
+                in_user_agents = false;
+                group.rules.insert(format!("{}: {}", kind.key(), String::from_utf8_lossy(&normalize_percent_encoding(line.value.as_bytes()))));
+            },
+            Some(DirectiveKind::CrawlDelay) => {
+                in_user_agents = false;
+                group.crawl_delay.get_or_insert(line.value.to_string());
+            },
+            Some(DirectiveKind::Sitemap) => {
+                sitemaps.insert(line.value);
+            },
+            Some(DirectiveKind::Host) => {
+                host.get_or_insert(line.value);
+            },
+            None => {},
+        }
+    }
+    groups.entry(user_agents).or_default().merge(group);
+
+    let mut sections = Vec::new();
+    for (user_agents, group) in &groups {
+        if user_agents.is_empty() && group.rules.is_empty() && group.crawl_delay.is_none() {
+            continue;
+        }
+        let lines: Vec<String> = user_agents.iter()
+            .map(|user_agent| format!("{}: {}", DirectiveKind::UserAgent.key(), user_agent))
+            .chain(group.crawl_delay.iter().map(|crawl_delay| format!("{}: {}", DirectiveKind::CrawlDelay.key(), crawl_delay)))
+            .chain(group.rules.iter().cloned())
+            .collect();
+        sections.push(lines.join("\n"));
+    }
+    let global_lines: Vec<String> = sitemaps.iter()
+        .map(|sitemap| format!("{}: {}", DirectiveKind::Sitemap.key(), sitemap))
+        .chain(host.map(|host| format!("{}: {}", DirectiveKind::Host.key(), host)))
+        .collect();
+    if !global_lines.is_empty() {
+        sections.push(global_lines.join("\n"));
+    }
+
+    let mut canonical = sections.join("\n\n");
+    if !canonical.is_empty() {
+        canonical.push('\n');
+    }
+    canonical
+}
+
+/// The rules of the groups naming a set of user agents
+#[derive(Default)]
+struct Group {
+    /// the first `Crawl-delay`, the one the matcher uses
+    crawl_delay: Option<String>,
+    rules: BTreeSet<String>,
+}
+
+impl Group {
+    /// Add a later group's rules, keeping the earlier crawl-delay
+    fn merge(&mut self, mut later: Group) {
+        self.rules.append(&mut later.rules);
+        if self.crawl_delay.is_none() {
+            self.crawl_delay = later.crawl_delay;
+        }
+    }
+}
+
+/// A stable hash of the robots.txt's [`canonical_rules`], as 16 hex digits
+///
+/// It is the same on every platform and release, so can be stored and compared against later.
+pub fn rule_set_hash(robots: &str) -> String {
+    format!("{:016x}", fnv1a(canonical_rules(robots).as_bytes()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cosmetic_edits_keep_the_hash() {
+        let robots = "User-agent: googlebot\nDisallow: /a\nAllow: /b\n";
+        let edited = "# comment\nuser-agent: Googlebot\n\nallow: /b   # why\nDisallow: /a\nDisallow: /a\n";
+        assert_eq!(rule_set_hash(robots), rule_set_hash(edited));
+    }
+
+    #[test]
+    fn rule_changes_change_the_hash() {
+        assert_ne!(rule_set_hash("User-agent: *\nDisallow: /a\n"), rule_set_hash("User-agent: *\nDisallow: /b\n"));
+    }
+
+    #[test]
+    fn only_the_first_crawl_delay_of_a_group_counts() {
+        let robots = "User-agent: a\nCrawl-delay: 1\nCrawl-delay: 2\n";
+        let swapped = "User-agent: a\nCrawl-delay: 2\nCrawl-delay: 1\n";
+        assert_ne!(rule_set_hash(robots), rule_set_hash(swapped));
+        assert_eq!(canonical_rules(robots), "user-agent: a\ncrawl-delay: 1\n");
+    }
+
+    #[test]
+    fn merged_groups_keep_the_earlier_crawl_delay() {
+        let robots = "User-agent: a\nCrawl-delay: 2\nDisallow: /x\n\nUser-agent: a\nCrawl-delay: 1\n";
+        assert_eq!(canonical_rules(robots), "user-agent: a\ncrawl-delay: 2\ndisallow: /x\n");
+    }
+}
@@ -0,0 +1,163 @@
+//! Comparing the decisions of two robots.txt files, to catch regressions from an edit
+use std::error::Error;
+
+use crate::{run_tests, TestCaseDefinition, TestRun};
+
+/// A test case whose allow/deny decision differs between the baseline and the new robots.txt
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DecisionChange {
+    pub user_agent: String,
+    pub url: String,
+    /// whether access was allowed by the baseline robots.txt
+    pub baseline_allowed: bool,
+    /// whether access is allowed by the new robots.txt
+    pub allowed: bool,
+}
+
+/// Evaluate the test cases against both robots.txt files and return the ones decided differently,
+/// in the order the test cases were given
+///
+/// Only the user agent and URL of each test case are used, the expectations are ignored along with
+/// crawl-delay only, sitemap and host test cases. Fails if either robots.txt can't be parsed for
+/// one of the user agents.
+pub fn compare_decisions(baseline: &[u8], robots: &[u8], cases: &[TestCaseDefinition]) -> Result<Vec<DecisionChange>, Box<dyn Error>> {
+    let cases: Vec<TestCaseDefinition> = cases.iter()
+        .filter(|test| test.checks_url() && !test.is_sitemap_assertion() && !test.is_host_assertion())
+        .cloned()
+        .collect();
+    let baseline_run = run_tests(baseline, &cases);
+    let run = run_tests(robots, &cases);
+    check_parsed(&baseline_run)?;
+    check_parsed(&run)?;
+
+    Ok(baseline_run.results.iter()
+        .zip(&run.results)
+        .filter(|(baseline_result, result)| baseline_result.actual_result() != result.actual_result())
+        .map(|(baseline_result, result)| DecisionChange {
+            user_agent: result.user_agent.clone(),
+            url: result.url.clone(),
+            baseline_allowed: baseline_result.actual_result(),
+            allowed: result.actual_result(),
+        })
+        .collect())
+}
+
+/// A test case URL decided differently once a trailing slash is added or removed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TrailingSlashAmbiguity {
+    pub user_agent: String,
+    /// URL as the test case gives it
+    pub url: String,
+    /// the URL with its path's trailing slash added or removed
+    pub variant_url: String,
+    /// whether access to `url` is allowed
+    pub allowed: bool,
+    /// line of the test case file the test case was read from, when the format tracks it
+    pub line_number: Option<u64>,
+}
+
+/// Evaluate each test case URL with and without a trailing slash on its path, returning the ones
+/// whose decision depends on it
+///
+/// Rules match the start of the path byte for byte, so `Disallow: /path/` doesn't block
+/// `/path` while `Disallow: /path` blocks both `/path/` and `/pathology`, and a `$` anchor
+/// matches only one of the two. Crawlers never add or remove the slash themselves. Like
+/// [`compare_decisions`], crawl-delay only, sitemap and host test cases are skipped, as is the
+/// root path, and it fails if the robots.txt can't be parsed for one of the user agents.
+pub fn trailing_slash_ambiguities(robots: &[u8], cases: &[TestCaseDefinition]) -> Result<Vec<TrailingSlashAmbiguity>, Box<dyn Error>> {
+    let (cases, variants): (Vec<TestCaseDefinition>, Vec<TestCaseDefinition>) = cases.iter()
+        .filter(|test| test.checks_url() && !test.is_sitemap_assertion() && !test.is_host_assertion())
+        .filter_map(|test| {
+            let variant_url = toggle_trailing_slash(&test.url)?;
+            Some((test.clone(), TestCaseDefinition { url: variant_url, ..test.clone() }))
+        })
+        .unzip();
+    let run = run_tests(robots, &cases);
+    check_parsed(&run)?;
+    let variant_run = run_tests(robots, &variants);
+
+    Ok(run.results.iter()
+        .zip(&variant_run.results)
+        .filter(|(result, variant_result)| result.actual_result() != variant_result.actual_result())
+        .map(|(result, variant_result)| TrailingSlashAmbiguity {
+            user_agent: result.user_agent.clone(),
+            url: result.url.clone(),
+            variant_url: variant_result.url.clone(),
+            allowed: result.actual_result(),
+            line_number: result.line_number,
+        })
+        .collect())
+}
+
+/// Fail with the parse error of the first errored test case, as a comparison needs every decision
+fn check_parsed(run: &TestRun) -> Result<(), Box<dyn Error>> {
+    match run.results.iter().find_map(|result| result.error.as_deref()) {
+        Some(error) => Err(error.into()),
+        None => Ok(()),
+    }
+}
+
+/// The URL with the trailing slash of its path removed, or added when it has none, `None` for
+/// the root path
+fn toggle_trailing_slash(url: &str) -> Option<String> {
+    let path_end = url.find(['?', '#']).unwrap_or(url.len());
+    let (path, rest) = url.split_at(path_end);
+    // The path of an absolute URL starts after its host
+    let host_end = path.find("://").map_or(0, |scheme_end| scheme_end + 3);
+    let has_path = |url: &str| url.get(host_end..).is_some_and(|path| path.contains('/'));
+    if !has_path(path) {
+        return None;
+    }
+    let toggled = match path.strip_suffix('/') {
+        Some(stripped) if has_path(stripped) => stripped.to_string(),
+        Some(_) => return None,
+        None => format!("{}/", path),
+    };
+    Some(format!("{}{}", toggled, rest))
+}
+
+/// A test case URL the `robotstxt` crate, a port of Google's parser, decides differently than
+/// `texting_robots`
+#[cfg(feature = "compare-parsers")]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParserDisagreement {
+    pub user_agent: String,
+    pub url: String,
+    /// whether `texting_robots` allows access
+    pub allowed: bool,
+    /// whether `robotstxt` allows access
+    pub robotstxt_allowed: bool,
+    /// line of the test case file the test case was read from, when the format tracks it
+    pub line_number: Option<u64>,
+}
+
+/// Evaluate the test cases with both `texting_robots` and `robotstxt`, returning the ones they
+/// decide differently, in the order the test cases were given
+///
+/// Both get the URL normalised the same way, see [`crate::normalize_url`]. Like
+/// [`compare_decisions`], crawl-delay only, sitemap and host test cases are skipped, and it fails
+/// if `texting_robots` can't parse the robots.txt for one of the user agents.
+#[cfg(feature = "compare-parsers")]
+pub fn parser_disagreements(robots: &[u8], cases: &[TestCaseDefinition]) -> Result<Vec<ParserDisagreement>, Box<dyn Error>> {
+    let cases: Vec<TestCaseDefinition> = cases.iter()
+        .filter(|test| test.checks_url() && !test.is_sitemap_assertion() && !test.is_host_assertion())
+        .cloned()
+        .collect();
+    let run = run_tests(robots, &cases);
+    check_parsed(&run)?;
+    let robots = String::from_utf8_lossy(robots);
+
+    Ok(run.results.iter()
+        .filter_map(|result| {
+            let url = crate::normalize_url(&result.url);
+            let robotstxt_allowed = robotstxt::DefaultMatcher::default().one_agent_allowed_by_robots(&robots, &result.user_agent, &url);
+            (robotstxt_allowed != result.actual_result()).then(|| ParserDisagreement {
+                user_agent: result.user_agent.clone(),
+                url: result.url.clone(),
+                allowed: result.actual_result(),
+                robotstxt_allowed,
+                line_number: result.line_number,
+            })
+        })
+        .collect())
+}
@@ -0,0 +1,53 @@
+//! Assembling one robots.txt from fragment files, such as one per section of a large site
+use std::error::Error;
+
+use crate::directives::{parse_lines, DirectiveKind, ParsedLine, RobotsLine};
+
+/// Concatenate `(name, content)` fragments in order, each ending with a line break
+///
+/// Fails if a group would run across two fragments, so a fragment only affects the user agents
+/// it names: one may not start with rules before its first `User-agent` line, as they would
+/// join the previous fragment's last group, nor end with `User-agent` lines without rules, as
+/// those user agents would share the next fragment's first group.
+pub fn compose(fragments: &[(&str, &[u8])]) -> Result<Vec<u8>, Box<dyn Error>> {
+    let mut composed = Vec::new();
+    for (index, (name, content)) in fragments.iter().enumerate() {
+        let text = String::from_utf8_lossy(content);
+        let group_lines: Vec<RobotsLine> = parse_lines(&text)
+            .filter_map(|line| match line {
+                ParsedLine::Directive(directive) if is_group_line(&directive) => Some(directive),
+                _ => None,
+            })
+            .collect();
+
+        let previous = index.checked_sub(1).map(|previous| fragments[previous].0);
+        if let (Some(first), Some(previous)) = (group_lines.first(), previous) {
+            if first.kind() != Some(DirectiveKind::UserAgent) {
+                return Err(format!(
+                    "fragment {} line {}: {:?} comes before any User-agent line, so would join the last group of fragment {}",
+                    name, first.line_number, first.key, previous
+                ).into());
+            }
+        }
+        let next = fragments.get(index + 1).map(|(next, _)| *next);
+        if let (Some(last), Some(next)) = (group_lines.last(), next) {
+            if last.kind() == Some(DirectiveKind::UserAgent) {
+                return Err(format!(
+                    "fragment {} line {}: User-agent line without rules after it, so would share the first group of fragment {}",
+                    name, last.line_number, next
+                ).into());
+            }
+        }
+
+        composed.extend_from_slice(content);
+        if !content.is_empty() && !content.ends_with(b"\n") && !content.ends_with(b"\r") {
+            composed.push(b'\n');
+        }
+    }
+    Ok(composed)
+}
+
+/// Whether the line opens a group or belongs to one, unlike `Sitemap` and `Host` lines
+fn is_group_line(line: &RobotsLine) -> bool {
+    matches!(line.kind(), Some(DirectiveKind::UserAgent | DirectiveKind::Allow | DirectiveKind::Disallow | DirectiveKind::CrawlDelay))
+}
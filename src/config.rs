@@ -0,0 +1,30 @@
+//! Default options read from a `robots-tester.toml` file
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+
+use serde::Deserialize;
+
+/// Name of the config file looked for in the working directory
+pub const CONFIG_FILE_NAME: &str = "robots-tester.toml";
+
+/// Defaults for options that aren't given on the command line, every key is optional
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    /// robots.txt file content path
+    pub robots_text_file_path: Option<String>,
+    /// test cases file content path or glob pattern
+    pub test_case_file_path: Option<String>,
+    /// format of the results, such as `text` or `json`
+    pub output_format: Option<String>,
+    /// whether to generate a JUnit test report
+    pub generate_test_report: Option<bool>,
+    /// minimum percentage of passing test cases
+    pub fail_under: Option<f64>,
+}
+
+/// Load a config file, failing on keys it doesn't know
+pub fn load_config(path: &Path) -> Result<Config, Box<dyn Error>> {
+    Ok(toml::from_str(&fs::read_to_string(path)?)?)
+}
@@ -0,0 +1,98 @@
+//! Which robots.txt rules the test cases exercise, like code coverage for robots.txt
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use crate::directives::{parse_lines, DirectiveKind, ParsedLine, RobotsLine};
+use crate::explain::{group_rules, pattern_matcher};
+use crate::{normalize_url, TestCaseOutput, HOST_USER_AGENT, SITEMAP_USER_AGENT, WILDCARD_USER_AGENT};
+
+/// An `Allow` or `Disallow` rule and how many test case URLs it matched
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RuleCoverage<'a> {
+    pub rule: RobotsLine<'a>,
+    /// number of test cases whose URL the rule matched, for a user agent whose groups hold it
+    pub matches: usize,
+}
+
+/// Count, for every `Allow` and `Disallow` rule in file order, the test case URLs it matched
+///
+/// A rule counts whether or not it decided the test case, as a longer rule may have won. Test
+/// cases that only assert the crawl-delay, a sitemap or the host match no rules.
+pub fn rule_coverage<'a>(robots: &'a str, test_results: &[TestCaseOutput]) -> Vec<RuleCoverage<'a>> {
+    let mut coverage: BTreeMap<usize, RuleCoverage> = parse_lines(robots)
+        .filter_map(|line| match line {
+            ParsedLine::Directive(rule) if matches!(rule.kind(), Some(DirectiveKind::Allow | DirectiveKind::Disallow)) => {
+                Some((rule.line_number, RuleCoverage { rule, matches: 0 }))
+            },
+            _ => None,
+        })
+        .collect();
+
+    let mut urls_by_agent: HashMap<&str, Vec<&str>> = HashMap::new();
+    for result in test_results {
+        if result.checks_url() && !result.is_sitemap_assertion() && !result.is_host_assertion() {
+            urls_by_agent.entry(&result.user_agent).or_default().push(&result.url);
+        }
+    }
+
+    // Each rule's matcher is built once and shared by every user agent whose groups hold it
+    let mut matchers = HashMap::new();
+    for (user_agent, urls) in urls_by_agent {
+        let (_, rules) = group_rules(robots, user_agent);
+        for rule in rules.iter().filter(|rule| matches!(rule.kind(), Some(DirectiveKind::Allow | DirectiveKind::Disallow))) {
+            let Some(matcher) = matchers.entry(rule.line_number).or_insert_with(|| pattern_matcher(rule.value)) else { continue };
+            let matched = urls.iter().filter(|url| !matcher.allowed(&normalize_url(url))).count();
+            if let Some(rule_coverage) = coverage.get_mut(&rule.line_number) {
+                rule_coverage.matches += matched;
+            }
+        }
+    }
+    coverage.into_values().collect()
+}
+
+/// The `User-agent` lines naming a group that none of `user_agents` is, in file order
+///
+/// User agents are compared ignoring case, as the matcher does, and `User-agent: *` is left out
+/// as it isn't named. An agent named on several lines is only reported on the first.
+pub fn uncovered_user_agents<'a, 'b>(robots: &'a str, user_agents: impl IntoIterator<Item = &'b str>) -> Vec<RobotsLine<'a>> {
+    let covered: HashSet<String> = user_agents.into_iter().map(str::to_lowercase).collect();
+    let mut reported = HashSet::new();
+
+    named_user_agent_lines(robots)
+        .filter(|directive| {
+            let user_agent = directive.value.to_lowercase();
+            !covered.contains(&user_agent) && reported.insert(user_agent)
+        })
+        .collect()
+}
+
+/// The test case user agents the robots.txt has no group of its own for, so get the `*` group's
+/// rules, in the order given
+///
+/// User agents are compared ignoring case, as the matcher does. `*`, sitemap and host
+/// assertions are left out, as is everything when the robots.txt names no user agent at all,
+/// since its rules then apply to everyone.
+pub fn undeclared_user_agents<'b>(robots: &str, user_agents: impl IntoIterator<Item = &'b str>) -> Vec<&'b str> {
+    let declared: HashSet<String> = named_user_agent_lines(robots).map(|directive| directive.value.to_lowercase()).collect();
+    if declared.is_empty() {
+        return Vec::new();
+    }
+    let mut reported = HashSet::new();
+
+    user_agents.into_iter()
+        .filter(|user_agent| ![WILDCARD_USER_AGENT, SITEMAP_USER_AGENT, HOST_USER_AGENT].contains(user_agent))
+        .filter(|user_agent| {
+            let user_agent = user_agent.to_lowercase();
+            !declared.contains(&user_agent) && reported.insert(user_agent)
+        })
+        .collect()
+}
+
+/// The `User-agent` lines naming a user agent, leaving out `User-agent: *`
+fn named_user_agent_lines(robots: &str) -> impl Iterator<Item = RobotsLine<'_>> {
+    parse_lines(robots)
+        .filter_map(|line| match line {
+            ParsedLine::Directive(directive) if directive.kind() == Some(DirectiveKind::UserAgent) => Some(directive),
+            _ => None,
+        })
+        .filter(|directive| directive.value != WILDCARD_USER_AGENT && !directive.value.is_empty())
+}
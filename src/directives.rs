@@ -0,0 +1,110 @@
+//! Line level parsing of raw robots.txt content, independent of the matcher
+//!
+//! `texting_robots` silently drops anything it doesn't understand, so features that need to
+//! reason about what the author actually wrote (linting, explaining decisions) work from here.
+
+/// A directive recognised by the standard and common extensions
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DirectiveKind {
+    UserAgent,
+    Allow,
+    Disallow,
+    Sitemap,
+    CrawlDelay,
+    Host,
+}
+
+const DIRECTIVE_KEYS: [(&str, DirectiveKind); 6] = [
+    ("user-agent", DirectiveKind::UserAgent),
+    ("allow", DirectiveKind::Allow),
+    ("disallow", DirectiveKind::Disallow),
+    ("sitemap", DirectiveKind::Sitemap),
+    ("crawl-delay", DirectiveKind::CrawlDelay),
+    ("host", DirectiveKind::Host),
+];
+
+impl DirectiveKind {
+    /// Look up a directive by its (case-insensitive) key
+    pub fn from_key(key: &str) -> Option<DirectiveKind> {
+        DIRECTIVE_KEYS.iter()
+            .find(|(name, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, kind)| *kind)
+    }
+
+    /// The directive's key, in lowercase
+    pub fn key(self) -> &'static str {
+        DIRECTIVE_KEYS.iter()
+            .find(|(_, kind)| *kind == self)
+            .map_or("", |(name, _)| name)
+    }
+}
+
+/// A `key: value` line with comments and surrounding whitespace removed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RobotsLine<'a> {
+    /// 1-based line number in the original content
+    pub line_number: usize,
+    pub key: &'a str,
+    pub value: &'a str,
+}
+
+impl RobotsLine<'_> {
+    /// The directive this line declares, if its key is a known one
+    pub fn kind(&self) -> Option<DirectiveKind> {
+        DirectiveKind::from_key(self.key)
+    }
+}
+
+/// A non-blank, non-comment line of robots.txt
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ParsedLine<'a> {
+    Directive(RobotsLine<'a>),
+    /// a line without a `key: value` separator
+    Malformed { line_number: usize, text: &'a str },
+}
+
+/// Split robots.txt content into its meaningful lines
+///
+/// Lines may end with `\n`, `\r\n` or a lone `\r`, as the matcher accepts all three.
+pub fn parse_lines(content: &str) -> impl Iterator<Item = ParsedLine<'_>> {
+    split_lines(content)
+        .enumerate()
+        .filter_map(|(index, line)| {
+            let line_number = index + 1;
+            let text = match line.find('#') {
+                Some(comment_start) => &line[..comment_start],
+                None => line,
+            }.trim();
+            if text.is_empty() {
+                return None;
+            }
+
+            Some(match text.split_once(':') {
+                Some((key, value)) => ParsedLine::Directive(RobotsLine {
+                    line_number,
+                    key: key.trim(),
+                    value: value.trim(),
+                }),
+                None => ParsedLine::Malformed { line_number, text },
+            })
+        })
+}
+
+/// Each line of the content, without its line ending
+pub fn split_lines(content: &str) -> impl Iterator<Item = &str> {
+    content.split('\n')
+        .flat_map(|line| line.strip_suffix('\r').unwrap_or(line).split('\r'))
+}
+
+/// The `Host` line declaring the canonical host, the first one as later ones are ignored
+pub fn declared_host(content: &str) -> Option<RobotsLine<'_>> {
+    host_lines(content).next()
+}
+
+/// Every `Host` line, in the order they appear
+pub fn host_lines(content: &str) -> impl Iterator<Item = RobotsLine<'_>> {
+    parse_lines(content).filter_map(|line| match line {
+        ParsedLine::Directive(directive) if directive.kind() == Some(DirectiveKind::Host) => Some(directive),
+        _ => None,
+    })
+}
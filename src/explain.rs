@@ -0,0 +1,176 @@
+//! Explaining the decision for a single URL, to debug why a test case fails
+//!
+//! `texting_robots` doesn't expose the rules it parsed, so the group a user agent falls into is
+//! worked out again from the raw lines, following the same rules as the matcher.
+use std::error::Error;
+
+use texting_robots::Robot;
+
+use crate::directives::{parse_lines, DirectiveKind, ParsedLine, RobotsLine};
+use crate::input::normalize_rule_escapes;
+use crate::{normalize_url, WILDCARD_USER_AGENT};
+
+/// Why a URL is allowed or denied for a user agent
+#[derive(Debug, Clone, PartialEq)]
+pub struct Explanation<'a> {
+    /// user agent whose groups apply, `*` when the requested one isn't named
+    pub group_user_agent: String,
+    /// whether fetching the URL is allowed
+    pub allowed: bool,
+    /// effective crawl-delay in seconds
+    pub crawl_delay: Option<f32>,
+    /// `Allow`, `Disallow` and `Crawl-delay` lines of the groups that apply
+    pub rules: Vec<RobotsLine<'a>>,
+    /// the longest `Allow` or `Disallow` rule matching the URL, if any matched
+    pub matching_rule: Option<RobotsLine<'a>>,
+}
+
+/// Explain whether `url` is allowed for `user_agent`
+///
+/// Fails if the robots.txt can't be parsed for the user agent.
+pub fn explain<'a>(robots: &'a str, user_agent: &str, url: &str) -> Result<Explanation<'a>, Box<dyn Error>> {
+    let url = normalize_url(url);
+    let r = Robot::new(user_agent, &normalize_rule_escapes(robots.as_bytes()))
+        .map_err(|e| format!("unable to parse robots.txt for user agent {}: {:#}", user_agent, e))?;
+    let (group_user_agent, rules) = group_rules(robots, user_agent);
+
+    let matching_rule = rules.iter()
+        .filter(|rule| matches!(rule.kind(), Some(DirectiveKind::Allow | DirectiveKind::Disallow)))
+        .filter(|rule| rule_matches(rule.value, &url))
+        // The longest rule wins, and Allow wins a tie as it sorts after Disallow
+        .max_by_key(|rule| (rule.value.len(), rule.kind() == Some(DirectiveKind::Allow)))
+        .copied();
+
+    Ok(Explanation {
+        group_user_agent,
+        allowed: r.allowed(&url),
+        crawl_delay: r.delay,
+        rules,
+        matching_rule,
+    })
+}
+
+/// The user agent whose groups apply to `user_agent`, and the `Allow`, `Disallow` and
+/// `Crawl-delay` lines of those groups
+pub(crate) fn group_rules<'a>(robots: &'a str, user_agent: &str) -> (String, Vec<RobotsLine<'a>>) {
+    let lines: Vec<RobotsLine> = parse_lines(robots)
+        .filter_map(|line| match line {
+            ParsedLine::Directive(directive) if directive.kind().is_some() => Some(directive),
+            _ => None,
+        })
+        .collect();
+
+    // Like the matcher, fall back to the `*` groups when the user agent isn't named
+    let user_agent = user_agent.to_lowercase();
+    let names_user_agent = lines.iter()
+        .any(|line| line.kind() == Some(DirectiveKind::UserAgent) && line.value.to_lowercase() == user_agent);
+    let group_user_agent = if names_user_agent { user_agent } else { WILDCARD_USER_AGENT.to_string() };
+
+    // Without any User-agent line every rule applies to everyone
+    let mut capturing = !lines.iter().any(|line| line.kind() == Some(DirectiveKind::UserAgent));
+    let mut in_user_agents = false;
+    let mut rules = Vec::new();
+    for line in lines {
+        match line.kind() {
+            Some(DirectiveKind::UserAgent) => {
+                // Consecutive User-agent lines share the group that follows them
+                if !in_user_agents {
+                    capturing = false;
+                }
+                in_user_agents = true;
+                capturing |= line.value.to_lowercase() == group_user_agent;
+            },
+            Some(DirectiveKind::Allow | DirectiveKind::Disallow | DirectiveKind::CrawlDelay) => {
+                in_user_agents = false;
+                if capturing {
+                    rules.push(line);
+                }
+            },
+            _ => {},
+        }
+    }
+    (group_user_agent, rules)
+}
+
+/// Whether a rule's path pattern matches the URL, checked with a robots.txt holding just that rule
+fn rule_matches(pattern: &str, url: &str) -> bool {
+    pattern_matcher(pattern).is_some_and(|r| !r.allowed(url))
+}
+
+/// A matcher that denies exactly the URLs a rule's path pattern matches, `None` for an empty
+/// pattern as it matches nothing
+pub(crate) fn pattern_matcher(pattern: &str) -> Option<Robot> {
+    if pattern.is_empty() {
+        return None;
+    }
+    let robots = format!("User-agent: *\nDisallow: {}\n", pattern);
+    Robot::new("*", &normalize_rule_escapes(robots.as_bytes())).ok()
+}
+
+/// How one `Allow` or `Disallow` rule's pattern was matched against a URL
+#[derive(Debug, Clone, PartialEq)]
+pub struct PatternTrace<'a> {
+    pub rule: RobotsLine<'a>,
+    /// whether the rule matches the URL
+    pub matches: bool,
+    /// the part of the URL path each `*` stood for, in order, when the rule matches
+    pub wildcards: Vec<String>,
+    /// whether the pattern ends with `$`, so must match up to the end of the URL
+    pub anchored: bool,
+}
+
+/// Trace every `Allow` and `Disallow` rule of an explanation against `url`
+///
+/// Whether a rule matches comes from the matcher, while what each `*` stood for is worked out
+/// again over the raw pattern, taking the leftmost match.
+pub fn trace_patterns<'a>(explanation: &Explanation<'a>, url: &str) -> Vec<PatternTrace<'a>> {
+    let url = normalize_url(url);
+    let path = url_path(&url);
+
+    explanation.rules.iter()
+        .filter(|rule| matches!(rule.kind(), Some(DirectiveKind::Allow | DirectiveKind::Disallow)))
+        .map(|rule| {
+            let matches = rule_matches(rule.value, &url);
+            let wildcards = if matches { expand_wildcards(rule.value, path).unwrap_or_default() } else { Vec::new() };
+            PatternTrace { rule: *rule, matches, wildcards, anchored: rule.value.ends_with('$') }
+        })
+        .collect()
+}
+
+/// The path and query of a URL, which is all rules are matched against
+fn url_path(url: &str) -> &str {
+    match url.split_once("://") {
+        Some((_, host_and_path)) => host_and_path.find(['/', '?']).map_or("/", |path_start| &host_and_path[path_start..]),
+        None => url,
+    }
+}
+
+/// What each `*` of the pattern stands for in `path`, or `None` if the pattern doesn't match it
+fn expand_wildcards(pattern: &str, path: &str) -> Option<Vec<String>> {
+    let (pattern, anchored) = match pattern.strip_suffix('$') {
+        Some(pattern) => (pattern, true),
+        None => (pattern, false),
+    };
+    let mut segments = pattern.split('*');
+    let first = segments.next().unwrap_or_default();
+    let mut rest = path.strip_prefix(first)?;
+    let segments: Vec<&str> = segments.collect();
+
+    let mut wildcards = Vec::new();
+    for (index, segment) in segments.iter().enumerate() {
+        let is_last = index + 1 == segments.len();
+        let segment_start = if is_last && (anchored || segment.is_empty()) {
+            // A trailing `*` stands for the rest of the path
+            rest.strip_suffix(segment)?.len()
+        } else {
+            rest.find(segment)?
+        };
+        wildcards.push(rest[..segment_start].to_string());
+        rest = &rest[segment_start + segment.len()..];
+    }
+    // Without a `*` the anchor still needs the whole path to be used
+    if anchored && segments.is_empty() && !rest.is_empty() {
+        return None;
+    }
+    Some(wildcards)
+}
@@ -0,0 +1,134 @@
+//! Fetching robots.txt over HTTP(S)
+use std::error::Error;
+use std::io::Read;
+use std::thread;
+use std::time::Duration;
+
+use time::format_description::well_known::Rfc2822;
+use time::OffsetDateTime;
+
+/// Maximum number of redirects followed when fetching robots.txt
+const MAX_REDIRECTS: u32 = 5;
+
+/// Body used when the server errors, which Google treats as the whole site being disallowed
+const DISALLOW_ALL: &[u8] = b"User-agent: *\nDisallow: /\n";
+
+/// How often to retry a fetch that fails with a 5xx status or a transport error
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// retries after the first attempt, 2 by default
+    pub retries: u32,
+    /// wait before the first retry, doubled for each one after it, 500ms by default
+    pub delay: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy { retries: 2, delay: Duration::from_millis(500) }
+    }
+}
+
+/// A fetched robots.txt and how long the server says it may be reused for
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FetchedRobots {
+    pub body: Vec<u8>,
+    /// from the response's `Cache-Control` or `Expires` header, `None` when neither gives one.
+    /// `no-store` and `no-cache` give zero
+    pub max_age: Option<Duration>,
+}
+
+/// Fetch the robots.txt at `url`, sending `user_agent` as the request's User-Agent header
+///
+/// Mirrors Google's handling of unsuccessful responses: a 4xx status is treated as an empty
+/// robots.txt (allow all) and a 5xx status as a robots.txt disallowing everything. A 5xx status
+/// or transport error is only accepted once `retry` is exhausted, `on_retry` is told of each retry.
+pub fn fetch_robots(url: &str, user_agent: &str, retry: &RetryPolicy, on_retry: impl FnMut(&str)) -> Result<Vec<u8>, Box<dyn Error>> {
+    Ok(fetch_robots_with_max_age(url, user_agent, retry, on_retry)?.body)
+}
+
+/// Fetch the robots.txt at `url` like [`fetch_robots`], also reading the response's caching
+/// headers
+///
+/// The empty robots.txt standing in after a 4xx status has no max age, while the one disallowing
+/// everything after repeated 5xx statuses has a zero max age, as the server may soon recover.
+pub fn fetch_robots_with_max_age(url: &str, user_agent: &str, retry: &RetryPolicy, mut on_retry: impl FnMut(&str)) -> Result<FetchedRobots, Box<dyn Error>> {
+    let agent = ureq::AgentBuilder::new()
+        .redirects(MAX_REDIRECTS)
+        .user_agent(user_agent)
+        .build();
+
+    let mut delay = retry.delay;
+    let mut attempt = 0;
+    loop {
+        attempt += 1;
+        let failure = match agent.get(url).call() {
+            Ok(response) => {
+                let max_age = max_age(response.header("Cache-Control"), response.header("Expires"));
+                let mut body = Vec::new();
+                response.into_reader().read_to_end(&mut body)?;
+                return Ok(FetchedRobots { body, max_age });
+            },
+            Err(ureq::Error::Status(code, _)) if (400..500).contains(&code) => return Ok(FetchedRobots { body: Vec::new(), max_age: None }),
+            Err(ureq::Error::Status(code, _)) if (500..600).contains(&code) => FetchFailure::ServerError(code),
+            Err(ureq::Error::Status(code, _)) => return Err(format!("unexpected HTTP status {} fetching {}", code, url).into()),
+            Err(ureq::Error::Transport(e)) => FetchFailure::Transport(e.to_string()),
+        };
+
+        if attempt > retry.retries {
+            // A server that keeps erroring disallows everything, one that can't be reached is an error
+            return match failure {
+                FetchFailure::ServerError(_) => Ok(FetchedRobots { body: DISALLOW_ALL.to_vec(), max_age: Some(Duration::ZERO) }),
+                FetchFailure::Transport(e) => Err(format!("unable to fetch {}: {}", url, e).into()),
+            };
+        }
+        on_retry(&format!(
+            "attempt {} of {} to fetch {} failed: {}, retrying in {}ms",
+            attempt,
+            retry.retries + 1,
+            url,
+            failure.description(),
+            delay.as_millis()
+        ));
+        thread::sleep(delay);
+        delay *= 2;
+    }
+}
+
+/// How long a response may be reused, preferring `Cache-Control` to `Expires` as HTTP does
+fn max_age(cache_control: Option<&str>, expires: Option<&str>) -> Option<Duration> {
+    if let Some(cache_control) = cache_control {
+        let directives: Vec<(&str, &str)> = cache_control.split(',')
+            .map(|directive| directive.trim().split_once('=').unwrap_or((directive.trim(), "")))
+            .collect();
+        let is_named = |name: &str, expected: &str| name.trim().eq_ignore_ascii_case(expected);
+        if directives.iter().any(|(name, _)| is_named(name, "no-store") || is_named(name, "no-cache")) {
+            return Some(Duration::ZERO);
+        }
+        let max_age = directives.iter()
+            .find(|(name, _)| is_named(name, "max-age"))
+            .and_then(|(_, value)| value.trim().trim_matches('"').parse().ok());
+        if let Some(seconds) = max_age {
+            return Some(Duration::from_secs(seconds));
+        }
+    }
+
+    // An Expires date that can't be parsed means already expired
+    let expires = expires?;
+    let remaining = OffsetDateTime::parse(expires, &Rfc2822).map_or(time::Duration::ZERO, |expires| expires - OffsetDateTime::now_utc());
+    Some(remaining.try_into().unwrap_or(Duration::ZERO))
+}
+
+/// A failed attempt that is worth retrying
+enum FetchFailure {
+    ServerError(u16),
+    Transport(String),
+}
+
+impl FetchFailure {
+    fn description(&self) -> String {
+        match self {
+            FetchFailure::ServerError(code) => format!("HTTP status {}", code),
+            FetchFailure::Transport(e) => e.clone(),
+        }
+    }
+}
@@ -0,0 +1,182 @@
+//! Decoding robots.txt content as it was stored or served
+use std::borrow::Cow;
+use std::io::{self, Read};
+use std::path::Path;
+
+use flate2::read::GzDecoder;
+
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+
+const UTF8_BOM: [u8; 3] = [0xef, 0xbb, 0xbf];
+
+/// Google only parses the first 500 KiB of a robots.txt and ignores the rest
+pub const GOOGLE_SIZE_LIMIT: usize = 500 * 1024;
+
+/// Decompress gzip compressed robots.txt content, leaving anything else untouched
+///
+/// Content counts as compressed when `path` ends in `.gz` or it starts with the gzip magic bytes.
+/// Fails with [`io::ErrorKind::InvalidData`] if compressed content isn't valid gzip, such as when
+/// it was cut short.
+pub fn decompress_robots(content: Vec<u8>, path: Option<&Path>) -> io::Result<Vec<u8>> {
+    let has_gz_extension = path
+        .and_then(|path| path.extension())
+        .is_some_and(|extension| extension.eq_ignore_ascii_case("gz"));
+    if !has_gz_extension && !content.starts_with(&GZIP_MAGIC) {
+        return Ok(content);
+    }
+
+    let mut decompressed = Vec::new();
+    // Reading from memory only fails on the content, which a truncated stream reports as an
+    // unexpected end of file
+    GzDecoder::new(content.as_slice())
+        .read_to_end(&mut decompressed)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    Ok(decompressed)
+}
+
+/// Remove a leading UTF-8 byte order mark, which would otherwise become part of the first
+/// directive, returning whether there was one
+pub fn strip_bom(content: &mut Vec<u8>) -> bool {
+    if !content.starts_with(&UTF8_BOM) {
+        return false;
+    }
+    content.drain(..UTF8_BOM.len());
+    true
+}
+
+/// The 1-based line of the first byte that isn't valid UTF-8, if there is one
+pub fn first_invalid_utf8_line(content: &[u8]) -> Option<usize> {
+    let valid_up_to = std::str::from_utf8(content).err()?.valid_up_to();
+    let valid = &content[..valid_up_to];
+    // A lone \r ends a line too, but the \r of a \r\n doesn't count again
+    let line_breaks = valid.iter()
+        .enumerate()
+        .filter(|&(index, &byte)| byte == b'\n' || (byte == b'\r' && content.get(index + 1) != Some(&b'\n')))
+        .count();
+    Some(line_breaks + 1)
+}
+
+/// Normalise the percent-encoding of a URL or path pattern the way RFC 9309 compares them
+///
+/// An escape of an unreserved character (a letter, digit, `-`, `.`, `_` or `~`) is decoded, so
+/// `/a%62c` becomes `/abc`, while any other escape is kept with its hex digits uppercased, so
+/// `%2f` becomes `%2F` and still doesn't match a `/`. A `%` not followed by two hex digits is
+/// left as it is.
+pub fn normalize_percent_encoding(input: &[u8]) -> Cow<'_, [u8]> {
+    if !input.contains(&b'%') {
+        return Cow::Borrowed(input);
+    }
+
+    let mut normalized = Vec::with_capacity(input.len());
+    let mut index = 0;
+    while index < input.len() {
+        let escaped = input.get(index + 1..index + 3)
+            .filter(|_| input[index] == b'%')
+            .and_then(|hex| std::str::from_utf8(hex).ok())
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match escaped {
+            Some(byte) if byte.is_ascii_alphanumeric() || matches!(byte, b'-' | b'.' | b'_' | b'~') => normalized.push(byte),
+            Some(byte) => normalized.extend_from_slice(format!("%{:02X}", byte).as_bytes()),
+            None => {
+                normalized.push(input[index]);
+                index += 1;
+                continue;
+            },
+        }
+        index += 3;
+    }
+    Cow::Owned(normalized)
+}
+
+/// Normalise the percent-encoding of every `Allow` and `Disallow` pattern with
+/// [`normalize_percent_encoding`], leaving the other lines untouched
+///
+/// The matcher compares patterns with URLs byte for byte, so both are normalised before matching.
+pub fn normalize_rule_escapes(content: &[u8]) -> Cow<'_, [u8]> {
+    if !content.contains(&b'%') {
+        return Cow::Borrowed(content);
+    }
+
+    let mut normalized = Vec::with_capacity(content.len());
+    for line in content.split_inclusive(|&byte| byte == b'\n' || byte == b'\r') {
+        let is_rule = line.iter().position(|&byte| byte == b':').is_some_and(|key_end| {
+            let key = line[..key_end].trim_ascii();
+            key.eq_ignore_ascii_case(b"allow") || key.eq_ignore_ascii_case(b"disallow")
+        });
+        if is_rule {
+            normalized.extend_from_slice(&normalize_percent_encoding(line));
+        } else {
+            normalized.extend_from_slice(line);
+        }
+    }
+    Cow::Owned(normalized)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use flate2::write::GzEncoder;
+    use flate2::Compression;
+
+    use super::*;
+
+    const ROBOTS: &[u8] = b"User-agent: *\nDisallow: /private\n";
+
+    fn gzip(content: &[u8]) -> Vec<u8> {
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(content).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    #[test]
+    fn gzipped_robots_txt_is_decompressed() {
+        assert_eq!(decompress_robots(gzip(ROBOTS), Some(Path::new("robots.txt.gz"))).unwrap(), ROBOTS);
+        // The magic bytes are enough without the extension
+        assert_eq!(decompress_robots(gzip(ROBOTS), None).unwrap(), ROBOTS);
+        assert_eq!(decompress_robots(ROBOTS.to_vec(), Some(Path::new("robots.txt"))).unwrap(), ROBOTS);
+    }
+
+    #[test]
+    fn leading_byte_order_mark_is_stripped() {
+        let mut content = b"\xef\xbb\xbfUser-agent: *\n".to_vec();
+        assert!(strip_bom(&mut content));
+        assert_eq!(content, b"User-agent: *\n");
+        assert!(!strip_bom(&mut content));
+    }
+
+    #[test]
+    fn invalid_utf8_is_found_by_line() {
+        assert_eq!(first_invalid_utf8_line(ROBOTS), None);
+        assert_eq!(first_invalid_utf8_line(b"User-agent: *\n# caf\xe9\nDisallow: /\n"), Some(2));
+        assert_eq!(first_invalid_utf8_line(b"a\r\nb\rc\n\xff"), Some(4));
+    }
+
+    #[test]
+    fn unreserved_escapes_are_decoded_and_reserved_ones_uppercased() {
+        assert_eq!(normalize_percent_encoding(b"/a%62c"), b"/abc".as_slice());
+        assert_eq!(normalize_percent_encoding(b"/%41%7a%2D%2e%5f%7E"), b"/Az-._~".as_slice());
+        assert_eq!(normalize_percent_encoding(b"/a%2fb%3f%c3%a9"), b"/a%2Fb%3F%C3%A9".as_slice());
+        assert_eq!(normalize_percent_encoding(b"/100%/%zz/%4"), b"/100%/%zz/%4".as_slice());
+        assert!(matches!(normalize_percent_encoding(b"/plain"), Cow::Borrowed(_)));
+    }
+
+    #[test]
+    fn only_rule_patterns_are_normalised() {
+        let robots = b"User-agent: bot%62\nDisallow: /a%62c\nallow : /%2f\nSitemap: https://example.com/%62.xml\n";
+        assert_eq!(
+            normalize_rule_escapes(robots),
+            b"User-agent: bot%62\nDisallow: /abc\nallow : /%2F\nSitemap: https://example.com/%62.xml\n".as_slice()
+        );
+    }
+
+    #[test]
+    fn corrupt_gzip_is_invalid_data() {
+        let error = decompress_robots(b"not gzip content".to_vec(), Some(Path::new("robots.txt.GZ"))).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+
+        let mut truncated = gzip(ROBOTS);
+        truncated.truncate(truncated.len() - 4);
+        assert_eq!(decompress_robots(truncated, None).unwrap_err().kind(), io::ErrorKind::InvalidData);
+    }
+}
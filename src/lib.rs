@@ -0,0 +1,625 @@
+//! Validate robots.txt files against a set of test cases.
+//!
+//! The binary is a thin wrapper over this crate, so the same checks can be embedded
+//! into other test suites without shelling out.
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet};
+use std::error::Error;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, PoisonError, RwLock};
+use std::time::{Duration, Instant};
+
+use texting_robots::Robot;
+use rayon::prelude::*;
+
+pub mod bench;
+pub mod cache;
+pub mod canonical;
+pub mod compare;
+pub mod compose;
+pub mod config;
+pub mod coverage;
+pub mod directives;
+pub mod explain;
+pub mod fetch;
+pub mod input;
+pub mod lint;
+pub mod manifest;
+pub mod report;
+pub mod self_test;
+pub mod test_cases;
+
+pub use test_cases::{dedupe_test_cases, expand_user_agents, load_test_case_file, load_test_cases, load_test_cases_from_csv, shuffle_test_cases, CsvOptions, TestCaseFile, TestCaseSource};
+
+/// User agent that evaluates a test case against the `User-agent: *` groups
+///
+/// A named agent only falls back to these groups when no group names it, so this is how any
+/// unnamed bot is treated. Rules before the first `User-agent` line only count, for every
+/// agent, when the file has no `User-agent` lines at all.
+pub const WILDCARD_USER_AGENT: &str = "*";
+
+/// User agent marking a test case that asserts `url` is (or isn't) a declared sitemap
+pub const SITEMAP_USER_AGENT: &str = "*sitemap*";
+
+/// User agent marking a test case that asserts `url` is (or isn't) the host declared by `Host`
+pub const HOST_USER_AGENT: &str = "*host*";
+
+/// A single expectation: fetching `url` as `user_agent` should be allowed or not
+///
+/// An empty `url` means the test case only asserts the crawl-delay. A `user_agent` of
+/// [`WILDCARD_USER_AGENT`] checks the rules every other bot gets, and one of
+/// [`SITEMAP_USER_AGENT`] asserts whether `url` is declared as a sitemap instead, and one of
+/// [`HOST_USER_AGENT`] whether it is the declared canonical host.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TestCaseDefinition {
+    /// user agent the robots.txt rules are evaluated for
+    pub user_agent: String,
+    /// URL or path to check, with its query string as rules such as `Disallow: /*?sort=` match it
+    pub url: String,
+    /// whether access is expected to be allowed, ignored when `url` is empty
+    pub expected_result: bool,
+    /// expected crawl-delay in seconds, `None` when it isn't asserted
+    pub expected_crawl_delay: Option<f32>,
+    /// line of the test case file the test case was read from, when the format tracks it
+    pub line_number: Option<u64>,
+    /// name reports use instead of the one generated from the expectations
+    pub name: Option<String>,
+    /// why the decision is expected, shown when the test case fails
+    pub reason: Option<String>,
+}
+
+impl TestCaseDefinition {
+    /// whether this test case asserts an allow/deny decision
+    pub fn checks_url(&self) -> bool {
+        !self.url.is_empty()
+    }
+
+    /// whether this test case asserts a sitemap declaration rather than access
+    pub fn is_sitemap_assertion(&self) -> bool {
+        self.user_agent == SITEMAP_USER_AGENT
+    }
+
+    /// whether this test case asserts the declared host rather than access
+    pub fn is_host_assertion(&self) -> bool {
+        self.user_agent == HOST_USER_AGENT
+    }
+}
+
+/// The outcome of evaluating a `TestCaseDefinition`
+#[derive(Debug, Clone, PartialEq)]
+pub struct TestCaseOutput {
+    /// user agent the robots.txt rules were evaluated for
+    pub user_agent: String,
+    /// URL or path that was checked
+    pub url: String,
+    /// whether access was expected to be allowed
+    pub expected_result: bool,
+    /// whether the actual decision matched the expected one, always true when no URL was checked
+    pub result: bool,
+    /// expected crawl-delay in seconds, `None` when it wasn't asserted
+    pub expected_crawl_delay: Option<f32>,
+    /// crawl-delay in seconds the robots.txt declares for the user agent
+    pub crawl_delay: Option<f32>,
+    /// line of the test case file the test case was read from, when the format tracks it
+    pub line_number: Option<u64>,
+    /// wall-clock time taken to match the URL
+    pub duration: Duration,
+    /// name reports use instead of the one generated from the expectations
+    pub name: Option<String>,
+    /// why the decision is expected, shown when the test case fails
+    pub reason: Option<String>,
+    /// why the test case couldn't be evaluated, such as the robots.txt not parsing for its user
+    /// agent. Such a test case neither passes nor fails
+    pub error: Option<String>,
+}
+
+impl TestCaseOutput {
+    /// whether access was actually allowed
+    pub fn actual_result(&self) -> bool {
+        if self.result { self.expected_result } else { !self.expected_result }
+    }
+
+    /// whether this test case asserted an allow/deny decision
+    pub fn checks_url(&self) -> bool {
+        !self.url.is_empty()
+    }
+
+    /// whether this test case asserted a sitemap declaration rather than access
+    pub fn is_sitemap_assertion(&self) -> bool {
+        self.user_agent == SITEMAP_USER_AGENT
+    }
+
+    /// whether this test case asserted the declared host rather than access
+    pub fn is_host_assertion(&self) -> bool {
+        self.user_agent == HOST_USER_AGENT
+    }
+
+    /// label for an expected or actual result, e.g. `allowed` or `declared`
+    pub fn result_label(&self, value: bool) -> &'static str {
+        match (self.is_sitemap_assertion() || self.is_host_assertion(), value) {
+            (true, true) => "declared",
+            (true, false) => "not declared",
+            (false, value) => decision_label(value),
+        }
+    }
+
+    /// whether the crawl-delay matched the expected one, always true when it wasn't asserted
+    pub fn crawl_delay_result(&self) -> bool {
+        self.expected_crawl_delay.is_none() || self.expected_crawl_delay == self.crawl_delay
+    }
+
+    /// whether every expectation of the test case held
+    pub fn passed(&self) -> bool {
+        !self.errored() && self.result && self.crawl_delay_result()
+    }
+
+    /// whether the test case couldn't be evaluated, see [`TestCaseOutput::error`]
+    pub fn errored(&self) -> bool {
+        self.error.is_some()
+    }
+}
+
+/// Counts over the results of a run
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TestRunSummary {
+    /// number of test cases run
+    pub total: usize,
+    /// number of test cases whose every expectation held
+    pub passed: usize,
+    /// number of test cases with at least one expectation that didn't hold
+    pub failed: usize,
+    /// number of test cases that couldn't be evaluated
+    pub errored: usize,
+    /// number of test cases whose allow/deny (or sitemap) decision didn't match
+    pub allow_mismatches: usize,
+    /// number of test cases whose crawl-delay didn't match
+    pub crawl_delay_mismatches: usize,
+    /// wall-clock time taken by the run
+    pub elapsed: Duration,
+}
+
+impl TestRunSummary {
+    /// Count the given results
+    pub fn from_results(results: &[TestCaseOutput], elapsed: Duration) -> TestRunSummary {
+        let passed = results.iter().filter(|n| n.passed()).count();
+        let errored = results.iter().filter(|n| n.errored()).count();
+        let evaluated = || results.iter().filter(|n| !n.errored());
+        TestRunSummary {
+            total: results.len(),
+            passed,
+            failed: results.len() - passed - errored,
+            errored,
+            allow_mismatches: evaluated().filter(|n| !n.result).count(),
+            crawl_delay_mismatches: evaluated().filter(|n| !n.crawl_delay_result()).count(),
+            elapsed,
+        }
+    }
+
+    /// Add the counts and time of another run, e.g. to total several suites
+    pub fn merge(&mut self, other: &TestRunSummary) {
+        self.total += other.total;
+        self.passed += other.passed;
+        self.failed += other.failed;
+        self.errored += other.errored;
+        self.allow_mismatches += other.allow_mismatches;
+        self.crawl_delay_mismatches += other.crawl_delay_mismatches;
+        self.elapsed += other.elapsed;
+    }
+
+    /// Percentage of passing test cases, `None` when there are none to avoid dividing by zero
+    pub fn pass_percentage(&self) -> Option<f64> {
+        if self.total == 0 {
+            return None;
+        }
+        Some(self.passed as f64 * 100.0 / self.total as f64)
+    }
+}
+
+/// The results of evaluating a set of test cases, along with their summary
+#[derive(Debug, Clone, PartialEq)]
+pub struct TestRun {
+    /// one result per evaluated test case, in the order the test cases were given
+    pub results: Vec<TestCaseOutput>,
+    /// totals of the results and how long evaluating them took
+    pub summary: TestRunSummary,
+    /// whether evaluation stopped on reaching the maximum number of failures, leaving test cases out
+    pub stopped_early: bool,
+}
+
+/// Evaluate every test case against the given robots.txt content
+///
+/// The test cases of a user agent the robots.txt can't be parsed for are errored, with the
+/// parse error, while the other user agents' test cases are still evaluated.
+pub fn run_tests(robots: &[u8], cases: &[TestCaseDefinition]) -> TestRun {
+    run_tests_with_progress(robots, cases, &AtomicUsize::new(0))
+}
+
+/// Like [`run_tests`], adding one to `completed` as each test case is evaluated
+///
+/// Lets another thread report how far a long run got.
+pub fn run_tests_with_progress(robots: &[u8], cases: &[TestCaseDefinition], completed: &AtomicUsize) -> TestRun {
+    run_tests_with_max_failures(robots, cases, completed, &AtomicUsize::new(0), usize::MAX)
+}
+
+/// Like [`run_tests_with_progress`], also adding one to `failed` for each failing test case and
+/// skipping the remaining test cases once it reaches `max_failures`
+///
+/// Test cases run in parallel, so a few more may fail before the others see the limit, and
+/// which of them are evaluated can differ between runs.
+pub fn run_tests_with_max_failures(robots: &[u8], cases: &[TestCaseDefinition], completed: &AtomicUsize, failed: &AtomicUsize, max_failures: usize) -> TestRun {
+    let start = Instant::now();
+    // Build one matcher per distinct user agent, as each agent may fall into a different group.
+    // Parsing dominates for suites with many agents, so the matchers are built in parallel too.
+    let user_agents: HashSet<&str> = cases.iter().map(|test| test.user_agent.as_str()).collect();
+    let parse_span = tracing::debug_span!("parse", user_agents = user_agents.len()).entered();
+    let rules = input::normalize_rule_escapes(robots);
+    // One user agent's group failing to parse leaves the others to be evaluated
+    let robots_by_agent: HashMap<&str, Result<Robot, String>> = user_agents.into_par_iter()
+        .map(|user_agent| {
+            let r = Robot::new(user_agent, &rules)
+                .map_err(|e| format!("unable to parse robots.txt for user agent {}: {:#}", user_agent, e));
+            (user_agent, r)
+        })
+        .collect();
+    parse_span.exit();
+    let robots_text = String::from_utf8_lossy(robots);
+    let host = directives::declared_host(&robots_text).map(|line| line.value);
+
+    let _evaluate_span = tracing::debug_span!("evaluate", test_cases = cases.len()).entered();
+    let test_results = cases.par_iter()
+        .filter_map(|test| {
+            if failed.load(Ordering::Relaxed) >= max_failures {
+                return None;
+            }
+            let r = match &robots_by_agent[test.user_agent.as_str()] {
+                Ok(r) => r,
+                Err(e) => {
+                    completed.fetch_add(1, Ordering::Relaxed);
+                    return Some(TestCaseOutput {
+                        result: false,
+                        expected_result: test.expected_result,
+                        url: test.url.clone(),
+                        user_agent: test.user_agent.clone(),
+                        expected_crawl_delay: test.expected_crawl_delay,
+                        crawl_delay: None,
+                        line_number: test.line_number,
+                        duration: Duration::ZERO,
+                        name: test.name.clone(),
+                        reason: test.reason.clone(),
+                        error: Some(e.clone()),
+                    });
+                },
+            };
+            let match_start = Instant::now();
+            let actual_result = if test.is_sitemap_assertion() {
+                r.sitemaps.iter().any(|sitemap| sitemap == &test.url)
+            } else if test.is_host_assertion() {
+                host.is_some_and(|host| ascii_host(host).eq_ignore_ascii_case(&ascii_host(&test.url)))
+            } else {
+                r.allowed(&normalize_url(&test.url))
+            };
+            let duration = match_start.elapsed();
+            completed.fetch_add(1, Ordering::Relaxed);
+            let output = TestCaseOutput {
+                result: !test.checks_url() || actual_result == test.expected_result,
+                expected_result: test.expected_result,
+                url: test.url.clone(),
+                user_agent: test.user_agent.clone(),
+                expected_crawl_delay: test.expected_crawl_delay,
+                crawl_delay: r.delay,
+                line_number: test.line_number,
+                duration,
+                name: test.name.clone(),
+                reason: test.reason.clone(),
+                error: None,
+            };
+            if !output.passed() {
+                failed.fetch_add(1, Ordering::Relaxed);
+            }
+            Some(output)
+        })
+        .collect::<Vec<TestCaseOutput>>();
+    let summary = TestRunSummary::from_results(&test_results, start.elapsed());
+    let stopped_early = test_results.len() < cases.len();
+    TestRun { results: test_results, summary, stopped_early }
+}
+
+/// Parsed robots.txt matchers for one robots.txt, built the first time each user agent asks
+///
+/// For evaluating many URLs against the same robots.txt without parsing it again for each. The
+/// cache can be shared between threads.
+#[derive(Debug)]
+pub struct RobotCache {
+    robots: Vec<u8>,
+    robots_by_agent: RwLock<HashMap<String, Arc<Robot>>>,
+}
+
+impl RobotCache {
+    /// Cache matchers for the given robots.txt content
+    pub fn new(robots: impl Into<Vec<u8>>) -> RobotCache {
+        RobotCache {
+            robots: input::normalize_rule_escapes(&robots.into()).into_owned(),
+            robots_by_agent: RwLock::new(HashMap::new()),
+        }
+    }
+
+    /// The matcher for a user agent, parsing the robots.txt for it if this is the first time
+    ///
+    /// Fails if the robots.txt can't be parsed for the user agent.
+    pub fn robot(&self, user_agent: &str) -> Result<Arc<Robot>, Box<dyn Error>> {
+        // The matcher ignores the case of the user agent, so the cache can too
+        let key = user_agent.to_lowercase();
+        if let Some(r) = self.robots_by_agent.read().unwrap_or_else(PoisonError::into_inner).get(&key) {
+            return Ok(Arc::clone(r));
+        }
+
+        let r = Robot::new(user_agent, &self.robots)
+            .map_err(|e| format!("unable to parse robots.txt for user agent {}: {:#}", user_agent, e))?;
+        let mut robots_by_agent = self.robots_by_agent.write().unwrap_or_else(PoisonError::into_inner);
+        Ok(Arc::clone(robots_by_agent.entry(key).or_insert_with(|| Arc::new(r))))
+    }
+
+    /// Whether fetching `url` is allowed for `user_agent`, normalising the URL like test cases
+    ///
+    /// Fails if the robots.txt can't be parsed for the user agent, leaving the fallback to the
+    /// caller.
+    pub fn allowed(&self, user_agent: &str, url: &str) -> Result<bool, Box<dyn Error>> {
+        Ok(self.robot(user_agent)?.allowed(&normalize_url(url)))
+    }
+}
+
+/// Normalise a test case URL so that bare paths and absolute URLs are matched the same way
+///
+/// Absolute URLs such as `https://example.com/admin` are kept, as the matcher only looks at their
+/// path and query, apart from an internationalized host such as `münchen.de` being converted to
+/// its punycode form. Anything without a scheme is a path on the robots.txt's own host: a missing
+/// leading `/` is added (`admin` becomes `/admin`), and the host of a scheme-relative
+/// `//example.com/admin` is dropped. Percent-encoding is normalised like the rules' patterns,
+/// see [`input::normalize_percent_encoding`], and raw Unicode is percent-encoded by the
+/// matcher, as crawlers do.
+///
+/// The query string is kept as it is, `?` and `&` included, since rules are matched against the
+/// path and query together: `Disallow: /*?sort=` denies `/shop?sort=asc` but not `/shop`, and
+/// `Disallow: /*&page=` denies `/shop?a=1&page=2` but not `/shop?page=2`. An escaped `%3F` or
+/// `%26` stays escaped, so doesn't match a rule's `?` or `&`.
+pub fn normalize_url(url: &str) -> Cow<'_, str> {
+    let url = normalize_url_form(url);
+    if !url.contains('%') {
+        return url;
+    }
+    Cow::Owned(String::from_utf8_lossy(&input::normalize_percent_encoding(url.as_bytes())).into_owned())
+}
+
+fn normalize_url_form(url: &str) -> Cow<'_, str> {
+    if has_scheme(url) {
+        return ascii_url_host(url);
+    }
+    if let Some(host_and_path) = url.strip_prefix("//") {
+        return match host_and_path.find(['/', '?']) {
+            Some(path_start) if host_and_path[path_start..].starts_with('/') => Cow::Borrowed(&host_and_path[path_start..]),
+            Some(path_start) => Cow::Owned(format!("/{}", &host_and_path[path_start..])),
+            None => Cow::Borrowed("/"),
+        };
+    }
+    if url.starts_with('/') {
+        Cow::Borrowed(url)
+    } else {
+        Cow::Owned(format!("/{}", url))
+    }
+}
+
+/// Convert the host of an absolute URL to punycode if it isn't ASCII
+fn ascii_url_host(url: &str) -> Cow<'_, str> {
+    let Some((scheme, rest)) = url.split_once("://") else { return Cow::Borrowed(url) };
+    let authority_end = rest.find(['/', '?', '#']).unwrap_or(rest.len());
+    let (authority, path) = rest.split_at(authority_end);
+    let (user_info, host_and_port) = match authority.rsplit_once('@') {
+        Some((user_info, host_and_port)) => (Some(user_info), host_and_port),
+        None => (None, authority),
+    };
+    let (host, port) = match host_and_port.rsplit_once(':') {
+        Some((host, port)) if port.chars().all(|c| c.is_ascii_digit()) => (host, Some(port)),
+        _ => (host_and_port, None),
+    };
+    let Cow::Owned(host) = ascii_host(host) else { return Cow::Borrowed(url) };
+
+    let user_info = user_info.map(|user_info| format!("{}@", user_info)).unwrap_or_default();
+    let port = port.map(|port| format!(":{}", port)).unwrap_or_default();
+    Cow::Owned(format!("{}://{}{}{}{}", scheme, user_info, host, port, path))
+}
+
+/// The punycode form of an internationalized host such as `münchen.de`, for comparing hosts
+///
+/// ASCII hosts, and ones that aren't valid domain names, are returned as they are.
+pub fn ascii_host(host: &str) -> Cow<'_, str> {
+    if host.is_ascii() {
+        return Cow::Borrowed(host);
+    }
+    match idna::domain_to_ascii(host) {
+        Ok(ascii) => Cow::Owned(ascii),
+        Err(_) => Cow::Borrowed(host),
+    }
+}
+
+/// Whether the URL starts with a `scheme://` such as `https://`
+fn has_scheme(url: &str) -> bool {
+    match url.split_once("://") {
+        Some((scheme, _)) => scheme.starts_with(|c: char| c.is_ascii_alphabetic())
+            && scheme.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '-' | '.')),
+        None => false,
+    }
+}
+
+/// Human readable name of a test case, used in reports
+///
+/// This is the test case's own name when it has one, and otherwise describes its expectations.
+pub fn get_test_case_name(result: &TestCaseOutput) -> String {
+    if let Some(name) = &result.name {
+        return name.clone();
+    }
+    if !result.checks_url() {
+        return format!("Crawl-delay for {} should be {}", result.user_agent, crawl_delay_label(result.expected_crawl_delay));
+    }
+    if result.is_sitemap_assertion() {
+        return format!("Sitemap {} should be {}", result.url, result.result_label(result.expected_result));
+    }
+    if result.is_host_assertion() {
+        return format!("Host {} should be {}", result.url, result.result_label(result.expected_result));
+    }
+
+    let name = format!("Accessing URL: {} as {} should be {}", result.url, result.user_agent, decision_label(result.expected_result));
+    match result.expected_crawl_delay {
+        Some(_) => format!("{} with crawl-delay {}", name, crawl_delay_label(result.expected_crawl_delay)),
+        None => name,
+    }
+}
+
+/// A crawl-delay in seconds, or `none` when it isn't set
+pub fn crawl_delay_label(crawl_delay: Option<f32>) -> String {
+    match crawl_delay {
+        Some(seconds) => format!("{}s", seconds),
+        None => "none".to_string(),
+    }
+}
+
+/// `allowed` or `denied`
+pub fn decision_label(allowed: bool) -> &'static str {
+    if allowed { "allowed" } else { "denied" }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_case(user_agent: &str, url: &str, expected_result: bool) -> TestCaseDefinition {
+        TestCaseDefinition {
+            user_agent: user_agent.to_string(),
+            url: url.to_string(),
+            expected_result,
+            expected_crawl_delay: None,
+            line_number: None,
+            name: None,
+            reason: None,
+        }
+    }
+
+    fn actual_results(robots: &str, cases: &[TestCaseDefinition]) -> Vec<bool> {
+        run_tests(robots.as_bytes(), cases).results.iter().map(TestCaseOutput::actual_result).collect()
+    }
+
+    #[test]
+    fn each_test_case_uses_its_own_user_agent() {
+        let robots = "User-agent: googlebot\nAllow: /private\n\nUser-agent: bingbot\nDisallow: /private\n";
+        let cases = [test_case("googlebot", "/private", true), test_case("bingbot", "/private", false)];
+        let test_run = run_tests(robots.as_bytes(), &cases);
+        assert_eq!(test_run.results.iter().map(TestCaseOutput::actual_result).collect::<Vec<_>>(), [true, false]);
+        assert_eq!(test_run.summary.passed, 2);
+    }
+
+    #[test]
+    fn user_agents_match_groups_ignoring_case() {
+        let robots = "User-agent: Googlebot\nDisallow: /private\n";
+        let cases = [test_case("GoogleBot", "/private", false), test_case("googlebot", "/private", false), test_case("GOOGLEBOT", "/", true)];
+        assert_eq!(actual_results(robots, &cases), [false, false, true]);
+    }
+
+    #[test]
+    fn wildcard_user_agent_gets_the_wildcard_group() {
+        let robots = "User-agent: googlebot\nDisallow: /google\n\nUser-agent: *\nDisallow: /everyone\n";
+        let cases = [
+            test_case(WILDCARD_USER_AGENT, "/everyone", false),
+            test_case(WILDCARD_USER_AGENT, "/google", true),
+            test_case("googlebot", "/google", false),
+            test_case("googlebot", "/everyone", true),
+            test_case("unknownbot", "/everyone", false),
+        ];
+        assert_eq!(actual_results(robots, &cases), [false, true, false, true, false]);
+    }
+
+    #[test]
+    fn rules_before_the_first_user_agent_only_count_without_groups() {
+        let with_group = "Disallow: /early\nUser-agent: googlebot\nDisallow: /g\n";
+        assert_eq!(actual_results(with_group, &[test_case(WILDCARD_USER_AGENT, "/early", true)]), [true]);
+        assert_eq!(actual_results("Disallow: /early\n", &[test_case(WILDCARD_USER_AGENT, "/early", false)]), [false]);
+    }
+
+    #[test]
+    fn bare_paths_and_absolute_urls_get_the_same_decision() {
+        assert_eq!(normalize_url("admin"), "/admin");
+        assert_eq!(normalize_url("/admin"), "/admin");
+        assert_eq!(normalize_url("https://example.com/admin"), "https://example.com/admin");
+        assert_eq!(normalize_url("//example.com/admin"), "/admin");
+        assert_eq!(normalize_url("//example.com?page=2"), "/?page=2");
+        assert_eq!(normalize_url("//example.com"), "/");
+
+        let robots = "User-agent: *\nDisallow: /admin\n";
+        let urls = ["/admin", "admin", "https://example.com/admin", "//example.com/admin", "/admin/users"];
+        let cases: Vec<TestCaseDefinition> = urls.iter().map(|url| test_case(WILDCARD_USER_AGENT, url, false)).collect();
+        assert_eq!(actual_results(robots, &cases), [false; 5]);
+    }
+
+    #[test]
+    fn internationalized_hosts_and_encoded_paths() {
+        assert_eq!(ascii_host("münchen.de"), "xn--mnchen-3ya.de");
+        assert_eq!(ascii_host("example.com"), "example.com");
+        assert_eq!(normalize_url("https://user@münchen.de:8080/a%20b?q=ü"), "https://user@xn--mnchen-3ya.de:8080/a%20b?q=ü");
+        assert_eq!(normalize_url("/a%20b"), "/a%20b");
+
+        let robots = "User-agent: *\nDisallow: /caf%C3%A9\nDisallow: /a%20b\n";
+        let urls = ["/café", "https://münchen.de/café", "/caf%c3%a9", "/a%20b", "https://münchen.de/a%20b/c", "/cafe"];
+        let cases: Vec<TestCaseDefinition> = urls.iter().map(|url| test_case(WILDCARD_USER_AGENT, url, false)).collect();
+        assert_eq!(actual_results(robots, &cases), [false, false, false, false, false, true]);
+    }
+
+    #[test]
+    fn encoded_unreserved_characters_match_their_plain_form() {
+        let robots = "User-agent: *\nDisallow: /a%62c\nDisallow: /x%2fy\n";
+        let urls = ["/abc", "/a%62c", "/%61bc", "/x%2Fy", "/x%2fy", "/x/y"];
+        let cases: Vec<TestCaseDefinition> = urls.iter().map(|url| test_case(WILDCARD_USER_AGENT, url, false)).collect();
+        // An escaped / is reserved, so doesn't match a real one
+        assert_eq!(actual_results(robots, &cases), [false, false, false, false, false, true]);
+    }
+
+    #[test]
+    fn crlf_and_cr_line_endings_match_like_lf() {
+        let robots = "User-agent: googlebot\nDisallow: /private\n\nUser-agent: *\nDisallow: /\nAllow: /public\n";
+        let cases = [
+            test_case("googlebot", "/private", false),
+            test_case("googlebot", "/public", true),
+            test_case("bingbot", "/private", false),
+            test_case("bingbot", "/public", true),
+        ];
+        let expected = actual_results(robots, &cases);
+        assert_eq!(expected, [false, true, false, true]);
+        assert_eq!(actual_results(&robots.replace('\n', "\r\n"), &cases), expected);
+        assert_eq!(actual_results(&robots.replace('\n', "\r"), &cases), expected);
+    }
+
+    #[test]
+    fn query_strings_are_matched_with_the_path() {
+        assert_eq!(normalize_url("shop?sort=asc&page=2"), "/shop?sort=asc&page=2");
+        assert_eq!(normalize_url("https://example.com/shop?sort=asc"), "https://example.com/shop?sort=asc");
+        assert_eq!(normalize_url("/shop%3Fsort=asc"), "/shop%3Fsort=asc");
+
+        let robots = "User-agent: *\nDisallow: /*?sort=\nDisallow: /*&page=\nDisallow: /search?q=$\n";
+        let cases = [
+            test_case(WILDCARD_USER_AGENT, "/shop?sort=asc", false),
+            test_case(WILDCARD_USER_AGENT, "/shop", true),
+            test_case(WILDCARD_USER_AGENT, "/shop?a=1&page=2", false),
+            test_case(WILDCARD_USER_AGENT, "/shop?page=2", true),
+            test_case(WILDCARD_USER_AGENT, "/search?q=", false),
+            test_case(WILDCARD_USER_AGENT, "/search?q=robots", true),
+            test_case(WILDCARD_USER_AGENT, "/shop%3Fsort=asc", true),
+            test_case(WILDCARD_USER_AGENT, "https://example.com/shop?b=2&page=3", false),
+        ];
+        assert_eq!(actual_results(robots, &cases), cases.iter().map(|test| test.expected_result).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn robot_cache_reuses_one_matcher_per_user_agent() {
+        let cache = RobotCache::new("User-agent: googlebot\nDisallow: /private\n");
+        assert!(!cache.allowed("googlebot", "private").unwrap());
+        assert!(cache.allowed("bingbot", "/private").unwrap());
+        assert!(Arc::ptr_eq(&cache.robot("googlebot").unwrap(), &cache.robot("Googlebot").unwrap()));
+    }
+}
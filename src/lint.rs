@@ -0,0 +1,235 @@
+//! Static checks over the raw robots.txt content
+use std::collections::BTreeSet;
+use std::fmt;
+
+use crate::directives::{host_lines, parse_lines, DirectiveKind, ParsedLine, RobotsLine};
+use crate::ascii_host;
+
+/// A problem found in robots.txt that the matcher would silently ignore
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LintWarning {
+    /// 1-based line number the warning refers to
+    pub line_number: usize,
+    pub message: String,
+}
+
+impl fmt::Display for LintWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.line_number, self.message)
+    }
+}
+
+/// Check for unknown directives, rules that aren't inside a `User-agent` group and conflicting
+/// `Host` lines
+pub fn lint(robots: &str) -> Vec<LintWarning> {
+    let mut warnings = Vec::new();
+    let mut seen_user_agent = false;
+
+    for line in parse_lines(robots) {
+        match line {
+            ParsedLine::Malformed { line_number, text } => warnings.push(LintWarning {
+                line_number,
+                message: format!("expected a \"key: value\" directive, found {:?}", text),
+            }),
+            ParsedLine::Directive(directive) => match directive.kind() {
+                None => warnings.push(LintWarning {
+                    line_number: directive.line_number,
+                    message: format!("unknown directive {:?}", directive.key),
+                }),
+                Some(DirectiveKind::UserAgent) => seen_user_agent = true,
+                Some(DirectiveKind::Allow | DirectiveKind::Disallow | DirectiveKind::CrawlDelay) if !seen_user_agent => {
+                    warnings.push(LintWarning {
+                        line_number: directive.line_number,
+                        message: format!("{:?} appears before any User-agent line", directive.key),
+                    })
+                },
+                Some(_) => {},
+            },
+        }
+    }
+    warnings.extend(check_hosts(robots));
+    warnings.extend(check_line_endings(robots));
+    warnings.sort_by_key(|warning| warning.line_number);
+    warnings
+}
+
+/// Check for lines ending differently than the first one, such as `\r\n` in a file of `\n` lines
+///
+/// The matcher accepts every kind, but a mix usually means the file was edited in several places
+/// and may not look to others as it does in the latest editor.
+pub fn check_line_endings(robots: &str) -> Vec<LintWarning> {
+    let mut endings = Vec::new();
+    let mut rest = robots;
+    while let Some(break_start) = rest.find(['\n', '\r']) {
+        let (ending, len) = if rest[break_start..].starts_with("\r\n") {
+            ("\\r\\n", 2)
+        } else if rest[break_start..].starts_with('\r') {
+            ("\\r", 1)
+        } else {
+            ("\\n", 1)
+        };
+        endings.push(ending);
+        rest = &rest[break_start + len..];
+    }
+
+    let Some(first) = endings.first().copied() else { return Vec::new() };
+    endings.iter()
+        .position(|ending| *ending != first)
+        .map(|index| LintWarning {
+            line_number: index + 1,
+            message: format!("mixed line endings, this line ends with {} while line 1 ends with {}", endings[index], first),
+        })
+        .into_iter()
+        .collect()
+}
+
+/// Check for `Host` lines declaring a different host than the first one, which is the one used
+pub fn check_hosts(robots: &str) -> Vec<LintWarning> {
+    let mut hosts = host_lines(robots);
+    let Some(first) = hosts.next() else { return Vec::new() };
+
+    hosts.filter(|host| !ascii_host(host.value).eq_ignore_ascii_case(&ascii_host(first.value)))
+        .map(|host| LintWarning {
+            line_number: host.line_number,
+            message: format!(
+                "Host {:?} conflicts with Host {:?} on line {}, which wins as the first one",
+                host.value,
+                first.value,
+                first.line_number
+            ),
+        })
+        .collect()
+}
+
+/// Check for test case user agents that only match a `User-agent` line when ignoring case
+///
+/// The matcher compares the whole user agent with each `User-agent` value ignoring case, as
+/// crawlers do, so these still match. This is for suites that want the spelling to agree too.
+pub fn check_user_agent_case<'a>(robots: &str, user_agents: impl IntoIterator<Item = &'a str>) -> Vec<LintWarning> {
+    let user_agents: BTreeSet<&str> = user_agents.into_iter().collect();
+    let mut warnings = Vec::new();
+
+    for line in parse_lines(robots) {
+        let ParsedLine::Directive(directive) = line else { continue };
+        if directive.kind() != Some(DirectiveKind::UserAgent) {
+            continue;
+        }
+        for user_agent in &user_agents {
+            if *user_agent != directive.value && user_agent.eq_ignore_ascii_case(directive.value) {
+                warnings.push(LintWarning {
+                    line_number: directive.line_number,
+                    message: format!(
+                        "User-agent {:?} only matches the test case user agent {:?} when ignoring case",
+                        directive.value,
+                        user_agent
+                    ),
+                });
+            }
+        }
+    }
+    warnings
+}
+
+/// Check each group for rules that contradict each other
+///
+/// These are an `Allow` and a `Disallow` of the same path, where the `Allow` always wins the
+/// tie, and an empty `Disallow` next to specific ones, which allows nothing as the specific
+/// rules are longer wherever they match.
+pub fn check_conflicts(robots: &str) -> Vec<LintWarning> {
+    let mut warnings = Vec::new();
+    let mut rules = Vec::new();
+    let mut in_user_agents = false;
+
+    for line in parse_lines(robots) {
+        let ParsedLine::Directive(directive) = line else { continue };
+        match directive.kind() {
+            Some(DirectiveKind::UserAgent) => {
+                // Consecutive User-agent lines share the group that follows them
+                if !in_user_agents {
+                    warnings.extend(group_conflicts(&rules));
+                    rules.clear();
+                }
+                in_user_agents = true;
+            },
+            Some(DirectiveKind::Allow | DirectiveKind::Disallow) => {
+                in_user_agents = false;
+                rules.push(directive);
+            },
+            Some(DirectiveKind::CrawlDelay) => in_user_agents = false,
+            _ => {},
+        }
+    }
+    warnings.extend(group_conflicts(&rules));
+    warnings
+}
+
+fn group_conflicts(rules: &[RobotsLine]) -> Vec<LintWarning> {
+    let mut warnings = Vec::new();
+
+    for (index, rule) in rules.iter().enumerate() {
+        if rule.value.is_empty() {
+            continue;
+        }
+        for earlier in rules[..index].iter().filter(|earlier| earlier.value == rule.value && earlier.kind() != rule.kind()) {
+            warnings.push(LintWarning {
+                line_number: rule.line_number,
+                message: format!(
+                    "\"{}: {}\" on line {} and \"{}: {}\" name the same path, the Allow wins the tie",
+                    earlier.key,
+                    earlier.value,
+                    earlier.line_number,
+                    rule.key,
+                    rule.value
+                ),
+            });
+        }
+    }
+
+    let is_disallow = |rule: &&RobotsLine| rule.kind() == Some(DirectiveKind::Disallow);
+    let specific_lines: Vec<String> = rules.iter()
+        .filter(is_disallow)
+        .filter(|rule| !rule.value.is_empty())
+        .map(|rule| rule.line_number.to_string())
+        .collect();
+    if !specific_lines.is_empty() {
+        for rule in rules.iter().filter(is_disallow).filter(|rule| rule.value.is_empty()) {
+            warnings.push(LintWarning {
+                line_number: rule.line_number,
+                message: format!(
+                    "empty Disallow allows nothing, the longer Disallow rules on {} {} still apply",
+                    if specific_lines.len() == 1 { "line" } else { "lines" },
+                    specific_lines.join(", ")
+                ),
+            });
+        }
+    }
+
+    warnings.sort_by_key(|warning| warning.line_number);
+    warnings
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mixed_line_endings_are_reported_once() {
+        assert_eq!(check_line_endings("User-agent: *\r\nDisallow: /a\r\n"), []);
+        assert_eq!(check_line_endings("User-agent: *\rDisallow: /a\r"), []);
+        let warnings = check_line_endings("User-agent: *\nDisallow: /a\r\nDisallow: /b\rDisallow: /c\n");
+        assert_eq!(warnings, [LintWarning {
+            line_number: 2,
+            message: "mixed line endings, this line ends with \\r\\n while line 1 ends with \\n".to_string(),
+        }]);
+    }
+
+    #[test]
+    fn user_agents_only_matching_ignoring_case_are_reported() {
+        let robots = "User-agent: Googlebot\nDisallow: /private\n\nUser-agent: bingbot\nDisallow: /\n";
+        let warnings = check_user_agent_case(robots, ["GoogleBot", "bingbot", "Googlebot"]);
+        assert_eq!(warnings, [LintWarning {
+            line_number: 1,
+            message: "User-agent \"Googlebot\" only matches the test case user agent \"GoogleBot\" when ignoring case".to_string(),
+        }]);
+    }
+}
@@ -1,160 +1,2545 @@
-use std::io::Write;
-use std::path::Path;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::collections::hash_map::RandomState;
+use std::fmt::Display;
+use std::hash::{BuildHasher, Hasher};
+use std::io::{self, IsTerminal, Read, Write};
+use std::num::NonZeroUsize;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::sync::Arc;
+use std::thread;
 use std::{fs::{self, File}, error::Error};
 use std::process::ExitCode;
-use clap::Parser;
-use junit_report::{Duration, ReportBuilder, TestCase, TestCaseBuilder, TestSuiteBuilder};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use anstyle::{AnsiColor, Color, Style};
+use clap::error::ErrorKind;
+use clap::builder::Resettable;
+use clap::parser::ValueSource;
+use clap::{ArgAction, CommandFactory, FromArgMatches, Parser, Subcommand, ValueEnum};
+use indicatif::{ProgressBar, ProgressStyle};
+use notify::{RecursiveMode, Watcher};
 
-use lenient_bool::LenientBool;
 use texting_robots::Robot;
-use rayon::prelude::*;
+use time::OffsetDateTime;
+use tracing::{debug, debug_span, info, warn};
+use tracing_subscriber::fmt::format::FmtSpan;
+use tracing_subscriber::EnvFilter;
+use url::Url;
+use robots_txt_tester::bench::bench;
+use robots_txt_tester::cache::FetchCache;
+use robots_txt_tester::canonical::{canonical_rules, rule_set_hash};
+use robots_txt_tester::compare::{compare_decisions, trailing_slash_ambiguities};
+#[cfg(feature = "compare-parsers")]
+use robots_txt_tester::compare::parser_disagreements;
+use robots_txt_tester::compose::compose;
+use robots_txt_tester::config::{load_config, Config, CONFIG_FILE_NAME};
+use robots_txt_tester::coverage::{rule_coverage, uncovered_user_agents, undeclared_user_agents};
+use robots_txt_tester::directives::declared_host;
+use robots_txt_tester::explain::{explain, trace_patterns};
+use robots_txt_tester::fetch::{fetch_robots, fetch_robots_with_max_age, RetryPolicy};
+use robots_txt_tester::input::{decompress_robots, first_invalid_utf8_line, normalize_rule_escapes, strip_bom, GOOGLE_SIZE_LIMIT};
+use robots_txt_tester::lint::{check_conflicts, check_hosts, check_user_agent_case, lint, LintWarning};
+use robots_txt_tester::manifest::load_manifest;
+use robots_txt_tester::report::{diff_against_report, merge_junit_reports, read_junit_outcomes, report_order, JunitSuite, ReportDelta, write_github_annotations, write_html, write_json, write_json_agent_rules, write_json_explanation, write_json_summary, write_json_url_decisions, write_csv_url_decisions, write_junit_failures, write_junit_suites, write_ndjson, write_markdown, write_tap};
+use robots_txt_tester::self_test::generate_test_cases;
+use robots_txt_tester::{crawl_delay_label, decision_label, dedupe_test_cases, get_test_case_name, load_test_case_file, normalize_url, shuffle_test_cases, CsvOptions, run_tests, run_tests_with_max_failures, TestCaseDefinition, TestCaseFile, TestCaseOutput, TestCaseSource, TestRun, TestRunSummary, WILDCARD_USER_AGENT};
+
+/// How --invert changes what passing means, printed with the results
+const INVERTED_NOTE: &str = "--invert is set, so test cases pass when the robots.txt decides the opposite of the expected result";
+
+/// Path argument meaning "read from stdin"
+const STDIN_PATH: &str = "-";
+
+/// How a robots.txt given with --robots-inline is named in messages and reports
+const INLINE_ROBOTS_SOURCE: &str = "inline";
+
+/// Name of the files results are written to when no path is given, followed by the format's extension
+const DEFAULT_OUTPUT_FILE_STEM: &str = "robots-test-results";
+
+const PASSED_STYLE: Style = Style::new().fg_color(Some(Color::Ansi(AnsiColor::Green)));
+const FAILED_STYLE: Style = Style::new().fg_color(Some(Color::Ansi(AnsiColor::Red)));
+
+/// Exit status when --timeout is reached, the same one timeout(1) uses
+const TIMEOUT_EXIT_CODE: u8 = 124;
+
+/// Why a run didn't succeed, each with its own exit status so scripts can tell a robots.txt that
+/// fails its tests from a run that couldn't check it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ExitStatus {
+    /// a test case or check failed
+    TestFailure = 1,
+    /// the options given can't be used
+    Usage = 2,
+    /// a file couldn't be read or written
+    Io = 3,
+    /// a test case file, robots.txt or config file couldn't be parsed
+    Parse = 4,
+    /// robots.txt couldn't be fetched from its URL
+    Network = 5,
+}
+
+impl ExitStatus {
+    /// The status of an error reading an input file, telling files that can't be read from ones
+    /// that can't be parsed
+    fn of_input_error(e: &(dyn Error + 'static)) -> ExitStatus {
+        let is_io_error = match e.downcast_ref::<io::Error>() {
+            // Decompression reports corrupt content as invalid data
+            Some(e) => e.kind() != io::ErrorKind::InvalidData,
+            None => e.downcast_ref::<csv::Error>().is_some_and(|e| e.is_io_error())
+                || e.downcast_ref::<serde_json::Error>().is_some_and(|e| e.is_io()),
+        };
+        if is_io_error { ExitStatus::Io } else { ExitStatus::Parse }
+    }
+}
+
+impl From<ExitStatus> for ExitCode {
+    fn from(status: ExitStatus) -> ExitCode {
+        ExitCode::from(status as u8)
+    }
+}
+
+/// An error ending the run, with the status to exit with
+#[derive(Debug)]
+struct RunError {
+    status: ExitStatus,
+    message: String,
+}
+
+impl RunError {
+    fn new(status: ExitStatus, message: String) -> RunError {
+        RunError { status, message }
+    }
+
+    /// Print the error and return the exit code it ends the run with
+    fn report(self) -> ExitCode {
+        eprintln!("{}", self.message);
+        self.status.into()
+    }
+}
+
+/// Number of test cases evaluated so far, reported when --timeout is reached
+static TEST_CASES_COMPLETED: AtomicUsize = AtomicUsize::new(0);
+
+/// Number of test cases that failed in this run, across suites, checked against --max-failures
+static TEST_CASES_FAILED: AtomicUsize = AtomicUsize::new(0);
 
 /// Simple program to validate robots.txt files against test cases
-#[derive(Parser, Debug)]
+#[derive(Parser, Debug, Clone)]
 #[command(author, version, about, long_about = None)]
+#[command(subcommand_required = true, arg_required_else_help = true)]
+#[command(after_help = "Options can also be set with an environment variable named after them in upper case, such as \
+    ROBOTS_TEXT_FILE_PATH for --robots-text-file-path. Options given on the command line take precedence.
+
+Exit status:
+  0    success
+  1    a test case (or a --strict check) failed
+  2    invalid options
+  3    a file couldn't be read or written
+  4    a test case file, robots.txt or config file couldn't be parsed
+  5    robots.txt couldn't be fetched from --robots-url
+  124  --timeout was reached")]
 struct Args {
-    /// robots.txt file content path
-    #[arg(short, long)]
-    robots_text_file_path: String,
+    /// config file of default options [default: robots-tester.toml, if there is one]
+    #[arg(long, env, global = true)]
+    config: Option<String>,
+
+    /// robots.txt file content path, or - to read from stdin
+    #[arg(short, long, env, global = true, conflicts_with_all = ["robots_url", "site", "robots_inline"])]
+    robots_text_file_path: Option<String>,
+
+    /// robots.txt content given directly, with lines separated by newlines or a literal \n
+    #[arg(long, env, global = true, value_name = "CONTENT", conflicts_with_all = ["robots_url", "site"])]
+    robots_inline: Option<String>,
+
+    /// URL to fetch robots.txt from
+    #[arg(long, env, global = true, conflicts_with = "site")]
+    robots_url: Option<String>,
+
+    /// origin of a site such as https://example.com, to fetch its /robots.txt from
+    #[arg(long, env, global = true, value_name = "ORIGIN", value_parser = parse_site)]
+    site: Option<String>,
+
+    /// User-Agent header sent when fetching robots.txt from a URL
+    #[arg(long, env, global = true, default_value = concat!("robots-txt-tester/", env!("CARGO_PKG_VERSION")))]
+    user_agent_header: String,
+
+    /// times to retry fetching robots.txt after a 5xx status or connection failure
+    #[arg(long, env, global = true, default_value_t = RetryPolicy::default().retries)]
+    retries: u32,
+
+    /// milliseconds to wait before the first retry, doubling for each retry after it
+    #[arg(long, env, global = true, value_name = "MS", default_value_t = RetryPolicy::default().delay.as_millis() as u64)]
+    retry_delay: u64,
+
+    /// keep a fetched robots.txt in this directory, and reuse it on later runs until it expires
+    #[arg(long, env, global = true, value_name = "PATH")]
+    cache_dir: Option<String>,
+
+    /// seconds a cached robots.txt is reused for, unless the server's Cache-Control or Expires
+    /// header says otherwise
+    #[arg(long, env, global = true, value_name = "SECONDS", default_value_t = 3600)]
+    cache_ttl: u64,
+
+    /// write the results to this file instead of stdout
+    #[arg(long, env, global = true)]
+    output_file: Option<String>,
+
+    /// fail when linting produces warnings, the robots.txt is larger than Google's 500 KiB limit
+    /// or a test case URL is on another host than the fetched robots.txt
+    #[arg(long, env, global = true)]
+    strict: bool,
+
+    /// print the robots.txt exactly as the matcher is given it, after removing a byte order mark,
+    /// decompressing, truncating and normalising percent-encoding, to stderr or to the given
+    /// file as --print-effective-robots=PATH. Not used with --manifest
+    #[arg(long, env, global = true, num_args = 0..=1, require_equals = true, value_name = "PATH")]
+    print_effective_robots: Option<Option<String>>,
+
+    /// only test the first 500 KiB of a larger robots.txt, as that is all Google parses
+    #[arg(long, env, global = true)]
+    truncate_to_limit: bool,
+
+    /// print each failing test case, retried fetch and how long fetching, parsing and evaluating
+    /// took, repeat (-vv) to print passing test cases and trace logs too. RUST_LOG overrides
+    /// which logs are printed
+    #[arg(short, long, env, global = true, action = ArgAction::Count, conflicts_with = "quiet")]
+    verbose: u8,
+
+    /// print nothing to stdout and rely on the exit code, errors still go to stderr. Test runs
+    /// end with a `robots-test total=N passed=N failed=N errored=N elapsed_ms=N` line on stderr
+    #[arg(short, long, env, global = true)]
+    quiet: bool,
+
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand, Debug, Clone)]
+enum Command {
+    /// Run test cases against the robots.txt
+    Test(TestArgs),
+    /// Check the robots.txt for unknown directives, rules outside a User-agent group and
+    /// conflicting Host lines
+    Lint {
+        /// also check for rules in a group that contradict each other
+        #[arg(long, env)]
+        check_conflicts: bool,
+    },
+    /// Print whether a URL is allowed or denied for a user agent, without asserting either
+    Check {
+        /// user agent to check the URL for
+        user_agent: String,
+
+        /// URL or path to check
+        url: String,
+
+        /// also print the rule deciding it and the rules that apply, like the explain subcommand
+        #[arg(long, env)]
+        explain: bool,
+
+        /// format to print the explanation in
+        #[arg(long, env, value_enum, default_value_t = ExplainFormat::Text, requires = "explain")]
+        explain_format: ExplainFormat,
+    },
+    /// Explain whether a URL is allowed for a user agent, and which rule decides it
+    Explain {
+        /// URL to explain the decision for
+        url: String,
+
+        /// user agent to explain the decision for, * for the rules any unnamed bot gets
+        #[arg(long, env, default_value = WILDCARD_USER_AGENT)]
+        user_agent: String,
+
+        /// format to print the explanation in
+        #[arg(long, env, value_enum, default_value_t = ExplainFormat::Text)]
+        explain_format: ExplainFormat,
+    },
+    /// Show how each rule that applies to a user agent matches a path, and what its * and $ stand for
+    ExplainPattern {
+        /// URL or path to trace the rules against
+        path: String,
+
+        /// user agent whose rules are traced, * for the rules any unnamed bot gets
+        #[arg(long, env, default_value = WILDCARD_USER_AGENT)]
+        user_agent: String,
+    },
+    /// Print the sitemaps declared in the robots.txt
+    Sitemaps,
+    /// Print a hash of the robots.txt's rules that only changes when what they allow does, not on
+    /// comment, whitespace or ordering edits
+    Hash {
+        /// fail if the hash differs from this one, such as a hash stored by an earlier run
+        #[arg(long, env)]
+        expected_hash: Option<String>,
+
+        /// print the canonical form of the rules that is hashed instead of the hash
+        #[arg(long, env, conflicts_with = "expected_hash")]
+        canonical: bool,
+    },
+    /// Report the test cases whose allow/deny decision differs from a baseline robots.txt
+    Compare {
+        /// robots.txt the decisions are compared against, such as the currently deployed one
+        #[arg(long, env)]
+        baseline_robots_path: String,
+
+        /// test cases file content path whose user agents and URLs are compared, expected
+        /// results are ignored
+        #[arg(short, long, env)]
+        test_case_file_path: String,
+
+        #[command(flatten)]
+        loading: TestCaseLoadingArgs,
+    },
+    /// Print whether each URL of a list is allowed or denied for a user agent, without asserting
+    /// either, such as to audit which of a crawl's URLs a bot may fetch
+    Classify {
+        /// file listing one URL or path per line, - for stdin. Blank lines and lines starting
+        /// with # are skipped
+        #[arg(long, env)]
+        url_list: String,
+
+        /// user agent to decide the URLs for, * for the rules any unnamed bot gets
+        #[arg(long, env, default_value = WILDCARD_USER_AGENT)]
+        user_agent: String,
+
+        /// format to print the decisions in
+        #[arg(long, env, value_enum, default_value_t = ClassifyFormat::Text)]
+        classify_format: ClassifyFormat,
+    },
+    /// Measure how fast the matcher decides a URL, or the test cases' URLs, reporting matches per
+    /// second and the p50 and p99 latency of a match
+    Bench(BenchArgs),
+    /// Concatenate robots.txt fragments in order into one robots.txt, checking that no group
+    /// runs across two fragments, then lint it and optionally run test cases against it
+    Compose(ComposeArgs),
+}
+
+/// How test case files are read
+#[derive(clap::Args, Debug, Clone)]
+struct TestCaseLoadingArgs {
+    /// drop repeated test cases before running them, failing on conflicting ones
+    #[arg(long, env)]
+    dedupe: bool,
+
+    /// format of the test cases file, detected from its extension by default, or for stdin from
+    /// how it starts: `[` or `{` for JSON, `---` or `- ` for YAML and CSV otherwise
+    #[arg(long, env, value_enum)]
+    test_format: Option<TestCaseSource>,
+
+    /// the CSV test cases file has no header row, read its columns as
+    /// user_agent, url, expected_result and expected_crawl_delay in that order.
+    /// Otherwise columns are found by those names in the header
+    #[arg(long, env)]
+    no_header: bool,
+
+    /// field delimiter of CSV test case files, such as ; or \t
+    /// [default: tab for .tsv files, comma otherwise]
+    #[arg(long, env, value_parser = parse_delimiter)]
+    delimiter: Option<u8>,
+
+    /// user agent for CSV test cases that leave the user_agent column blank, or have no such column
+    #[arg(long, env)]
+    default_user_agent: Option<String>,
+
+    /// warn about test case URLs longer than this many characters, usually a row whose fields
+    /// got glued together, and fail with --strict
+    #[arg(long, env, value_name = "CHARS", default_value_t = 8192)]
+    max_url_length: usize,
+}
+
+#[derive(clap::Args, Debug, Clone)]
+struct BenchArgs {
+    /// URL or path to match
+    #[arg(required_unless_present = "test_case_file_path", conflicts_with = "test_case_file_path")]
+    url: Option<String>,
+
+    /// user agent to match the URL for
+    #[arg(long, env, default_value = WILDCARD_USER_AGENT)]
+    user_agent: String,
+
+    /// test cases file whose URLs are matched in turn, for their own user agents
+    #[arg(short, long, env)]
+    test_case_file_path: Option<String>,
+
+    /// seconds to measure for
+    #[arg(long, env, default_value_t = 3)]
+    seconds: u64,
+
+    /// seconds to match for before measuring, so caches and branch predictors are warm
+    #[arg(long, env, default_value_t = 1)]
+    warmup_seconds: u64,
+
+    #[command(flatten)]
+    loading: TestCaseLoadingArgs,
+}
+
+#[derive(clap::Args, Debug, Clone)]
+struct ComposeArgs {
+    /// robots.txt fragment files, in the order they are concatenated, or - to read one from stdin
+    #[arg(required = true)]
+    fragments: Vec<String>,
+
+    /// file to write the composed robots.txt to
+    #[arg(long, env)]
+    composed_path: String,
+
+    /// test cases file to run against the composed robots.txt
+    #[arg(short, long, env)]
+    test_case_file_path: Option<String>,
+
+    /// also check the composed robots.txt for rules in a group that contradict each other
+    #[arg(long, env)]
+    check_conflicts: bool,
+
+    #[command(flatten)]
+    loading: TestCaseLoadingArgs,
+}
+
+#[derive(clap::Args, Debug, Clone)]
+struct TestArgs {
+    /// manifest of robots.txt and test case file pairs to test in one run, as CSV, JSON or YAML
+    #[arg(long, env, conflicts_with = "test_case_file_path")]
+    manifest: Option<String>,
+
+    /// test cases file content path, a glob pattern such as 'tests/*.csv' to run several files,
+    /// or - to read from stdin
+    #[arg(short, long, env, required_unless_present_any = ["manifest", "self_test"])]
+    test_case_file_path: Option<String>,
+
+    /// instead of reading test cases, check each Allow and Disallow rule decides a URL built
+    /// from its own path the way it declares
+    #[arg(long, env, conflicts_with_all = ["manifest", "test_case_file_path", "count"])]
+    self_test: bool,
+
+    #[command(flatten)]
+    loading: TestCaseLoadingArgs,
+
+    /// generate test report
+    #[arg(short, long, env, default_value_t = false)]
+    generate_test_report: bool,
+
+    /// path of the generated test report, implies --generate-test-report
+    /// [default: <test case file stem>.robots-test-results.xml]
+    #[arg(long, env)]
+    report_path: Option<String>,
+
+    /// leave the passing test cases out of JUnit reports, keeping the suite totals
+    #[arg(long, env)]
+    only_failures: bool,
+
+    /// add the test suites to the test report if it already exists, instead of replacing it
+    #[arg(long, env)]
+    append: bool,
+
+    /// wait for the test report to reach the disk before exiting, which is slow and rarely
+    /// needed on CI runners that are thrown away after the run
+    #[arg(long, env)]
+    durable_report: bool,
+
+    /// also write the summary counts as JSON to this file, whatever the output format, creating
+    /// its directory if needed
+    #[arg(long, env, value_name = "PATH")]
+    summary_file: Option<String>,
+
+    /// directory to write the test report, summary file and output files to, creating it if
+    /// needed. Their relative paths are taken from it, and output formats other than text and
+    /// github without a file of their own are written to it as robots-test-results.<extension>
+    #[arg(long, env, value_name = "DIR")]
+    output_dir: Option<String>,
+
+    /// format of the results written to stdout or the output file, one of text, json, ndjson,
+    /// junit, tap, github, markdown or html. Repeat to write several formats from one run, giving
+    /// each a file of its own as FORMAT:PATH, such as --output-format text --output-format
+    /// junit:report.xml [default: github when running in GitHub Actions, text otherwise]
+    #[arg(long, env, value_name = "FORMAT[:PATH]", value_parser = parse_output_spec)]
+    output_format: Vec<OutputSpec>,
+
+    /// run the test cases of each file in a random order, to catch results that depend on it. The
+    /// order is decided by the given seed, or a random one that is printed so it can be repeated
+    #[arg(long, env, num_args = 0..=1, value_name = "SEED")]
+    shuffle: Option<Option<u64>>,
+
+    /// flip each test case's expected allow/deny, sitemap or host decision, so it passes when the
+    /// robots.txt decides the opposite, such as to document known wrong behaviour. Crawl-delay
+    /// expectations still have to hold
+    #[arg(long, env)]
+    invert: bool,
+
+    /// minimum percentage of passing test cases for the run to succeed, failing cases are still reported
+    #[arg(long, env, value_parser = parse_percentage, default_value_t = 100.0)]
+    fail_under: f64,
+
+    /// also evaluate each test case URL with its path's trailing slash added or removed, failing
+    /// before running the test cases if any is decided differently. Rules match the start of the
+    /// path byte for byte, so `Disallow: /path/` doesn't block `/path` while `Disallow: /path`
+    /// blocks `/path/`, and crawlers never add or remove the slash themselves
+    #[arg(long, env)]
+    strict_trailing_slash: bool,
+
+    /// also evaluate each test case URL with the robotstxt crate, a port of Google's parser,
+    /// failing before running the test cases if it decides any differently, such as to check
+    /// that switching matchers won't change what is allowed
+    #[cfg(feature = "compare-parsers")]
+    #[arg(long, env)]
+    compare_parsers: bool,
+
+    /// fail before running the test cases if a group the robots.txt names with a User-agent
+    /// line has no test case for that user agent, listing each of them
+    #[arg(long, env)]
+    require_all_agents_covered: bool,
+
+    /// compare with an earlier JUnit report, printing the test cases that newly fail or pass
+    /// after the summary. The run then only fails on new failures, including test cases missing
+    /// from the report, not on failures the report already had
+    #[arg(long, env, value_name = "PATH")]
+    diff_against_report: Option<String>,
+
+    /// succeed when there are no test cases to run, or a test case file has none, instead of failing
+    #[arg(long, env)]
+    allow_empty: bool,
+
+    /// colour the text summary, by default only when writing to a terminal and NO_COLOR isn't set
+    #[arg(long, env, value_enum, default_value_t = ColorMode::Auto)]
+    color: ColorMode,
+
+    /// re-run whenever the robots.txt or test case file changes, until interrupted with Ctrl-C
+    #[arg(long, env, conflicts_with_all = ["robots_url", "site", "manifest"])]
+    watch: bool,
+
+    /// match test case user agents to User-agent lines ignoring case, as crawlers do. The matcher
+    /// always ignores case, so false instead warns about user agents only matching ignoring case
+    #[arg(long, env, action = ArgAction::Set, default_value_t = true)]
+    case_insensitive_agent: bool,
+
+    /// don't draw a progress bar on stderr, which is otherwise shown on a terminal for text output
+    #[arg(long, env)]
+    no_progress: bool,
+
+    /// print the Allow or Disallow rule that decided each printed test case, printing failures
+    /// even without --verbose
+    #[arg(long, env)]
+    show_rule: bool,
+
+    /// print the canonical host declared by the robots.txt's Host line, warning about
+    /// conflicting ones
+    #[arg(long, env)]
+    show_host: bool,
+
+    /// after the summary, print how many test case URLs each Allow and Disallow rule matched,
+    /// flagging the rules no test case exercises
+    #[arg(long, env)]
+    pattern_coverage: bool,
+
+    /// after the summary, print how many of each user agent's test cases passed
+    #[arg(long, env)]
+    by_agent: bool,
+
+    /// instead of running the test cases, print for each of their user agents the rules that
+    /// apply and whether the root path is allowed, as text or with --output-format json as JSON.
+    /// A URL no rule matches is always allowed
+    #[arg(long, env, conflicts_with_all = ["manifest", "self_test", "count", "watch"])]
+    explain_all: bool,
+
+    /// only load the test cases and print how many there are for each user agent, without running them
+    #[arg(long, env, conflicts_with = "watch")]
+    count: bool,
+
+    /// give up when the run takes longer than this many seconds, exiting with status 124
+    #[arg(long, env, value_name = "SECONDS", conflicts_with = "watch")]
+    timeout: Option<u64>,
+
+    /// number of threads evaluating test cases, 1 for sequential runs
+    /// [default: the number of logical CPUs]
+    #[arg(short, long, env)]
+    jobs: Option<NonZeroUsize>,
+
+    /// stop evaluating test cases after this many have failed, across every test case file.
+    /// Test cases run in parallel, so a few more may fail before stopping, and which failures
+    /// are shown can differ between runs
+    #[arg(long, env, value_name = "N")]
+    max_failures: Option<NonZeroUsize>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+enum OutputFormat {
+    /// human readable summary
+    Text,
+    /// results and summary as a JSON object
+    Json,
+    /// one JSON object per line for each result, then a summary line
+    Ndjson,
+    /// JUnit XML report
+    Junit,
+    /// Test Anything Protocol stream
+    Tap,
+    /// GitHub Actions error annotations for failures, followed by the text summary
+    Github,
+    /// Markdown table, for pull request comments and wikis
+    Markdown,
+    /// self-contained HTML page, written to robots-test-results.html unless --output-file is given
+    Html,
+}
+
+impl OutputFormat {
+    /// Extension of the file the format is written to by default, `None` for the formats meant
+    /// for a terminal or CI log
+    fn file_extension(self) -> Option<&'static str> {
+        match self {
+            OutputFormat::Text | OutputFormat::Github => None,
+            OutputFormat::Json => Some("json"),
+            OutputFormat::Ndjson => Some("ndjson"),
+            OutputFormat::Junit => Some("xml"),
+            OutputFormat::Tap => Some("tap"),
+            OutputFormat::Markdown => Some("md"),
+            OutputFormat::Html => Some("html"),
+        }
+    }
+
+    /// GitHub Actions only turns workflow commands on stdout into annotations
+    fn detect(args: &Args) -> OutputFormat {
+        if args.output_file.is_none() && std::env::var_os("GITHUB_ACTIONS").is_some_and(|value| value == "true") {
+            OutputFormat::Github
+        } else {
+            OutputFormat::Text
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+enum ExplainFormat {
+    /// the decision, the rule deciding it and the rules that apply, one per line
+    Text,
+    /// object with the url, user_agent, allowed, matched_rule, crawl_delay and applicable_rules
+    Json,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+enum ClassifyFormat {
+    /// each URL followed by allowed or denied, then the totals
+    Text,
+    /// array of objects with the user agent, URL and whether it is allowed
+    Json,
+    /// user_agent, url and expected_result columns, which can be read back as a test case file
+    Csv,
+}
+
+/// A format to write the results in, and the file to write it to
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct OutputSpec {
+    format: OutputFormat,
+    /// file of its own, instead of --output-file or stdout
+    path: Option<String>,
+}
+
+impl TestArgs {
+    /// Each format to write the results in, and the file to write it to, `None` for stdout
+    ///
+    /// Formats without a file of their own are written to --output-file, or stdout. When neither
+    /// is given, HTML is written to a [`DEFAULT_OUTPUT_FILE_STEM`] file as it is no use on a
+    /// terminal, and so is every format with a file extension when there is an --output-dir.
+    fn outputs(&self, args: &Args) -> Vec<(OutputFormat, Option<String>)> {
+        let detected = [OutputSpec { format: OutputFormat::detect(args), path: None }];
+        let specs = if self.output_format.is_empty() { &detected[..] } else { &self.output_format[..] };
+        specs.iter()
+            .map(|spec| {
+                let path = spec.path.clone().or_else(|| args.output_file.clone()).or_else(|| {
+                    let extension = spec.format.file_extension()?;
+                    (spec.format == OutputFormat::Html || self.output_dir.is_some())
+                        .then(|| format!("{}.{}", DEFAULT_OUTPUT_FILE_STEM, extension))
+                });
+                (spec.format, path.map(|path| self.output_path(&path)))
+            })
+            .collect()
+    }
+
+    /// Path of the JUnit test report, `None` when none is generated
+    fn report_path(&self, report_name: &str) -> Option<String> {
+        if !self.generate_test_report && self.report_path.is_none() {
+            return None;
+        }
+        let report_path = self.report_path.clone()
+            .unwrap_or_else(|| format!("{}.robots-test-results.xml", report_name));
+        Some(self.output_path(&report_path))
+    }
+
+    /// A path to write a file to, taken from --output-dir when it is relative
+    fn output_path(&self, path: &str) -> String {
+        match &self.output_dir {
+            Some(output_dir) => Path::new(output_dir).join(path).to_string_lossy().into_owned(),
+            None => path.to_string(),
+        }
+    }
+
+    /// Whether any of the formats is the text summary
+    fn outputs_text(&self, args: &Args) -> bool {
+        self.outputs(args).iter().any(|(format, _)| *format == OutputFormat::Text)
+    }
+}
+
+/// One robots.txt file and the results of its test cases
+struct Suite {
+    /// JUnit test suite name
+    name: String,
+    /// what the test case file says its test cases test
+    description: Option<String>,
+    /// test case file the results came from, `-` for stdin
+    test_case_file_path: String,
+    /// position of the suite's results in the combined results of the run
+    results: Range<usize>,
+    /// robots.txt the test cases ran against, to look up the rules deciding them
+    robots: Arc<str>,
+    summary: TestRunSummary,
+    /// whether --max-failures left some of the test cases out
+    stopped_early: bool,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+enum ColorMode {
+    Auto,
+    Always,
+    Never,
+}
+
+impl ColorMode {
+    fn enabled(self, args: &Args) -> bool {
+        match self {
+            ColorMode::Always => true,
+            ColorMode::Never => false,
+            ColorMode::Auto => args.output_file.is_none()
+                && std::env::var_os("NO_COLOR").is_none_or(|value| value.is_empty())
+                && io::stdout().is_terminal(),
+        }
+    }
+}
+
+fn main() -> ExitCode {
+    let mut args = match parse_args() {
+        Ok(args) => args,
+        Err(e) => return e.report(),
+    };
+    if let Some(site) = &args.site {
+        args.robots_url = Some(format!("{}/robots.txt", site));
+    }
+    init_logging(&args);
+    if let Command::Test(test_args) = &args.command {
+        // Formats sharing a destination would be written over each other, or mixed up on stdout
+        let mut destinations = HashSet::new();
+        for (_, path) in test_args.outputs(&args) {
+            if !destinations.insert(path.clone()) {
+                let destination = path.unwrap_or_else(|| "stdout".to_string());
+                eprintln!("error: more than one --output-format writes to {}, give each a file of its own as FORMAT:PATH", destination);
+                return ExitStatus::Usage.into();
+            }
+        }
+    }
+    if let Command::Test(TestArgs { shuffle: Some(seed @ None), .. }) = &mut args.command {
+        // Picked once, so every file and --watch run is shuffled the same way
+        let random_seed = random_seed();
+        *seed = Some(random_seed);
+        if !args.quiet {
+            eprintln!("Shuffling test cases with seed {}, pass --shuffle {} to repeat this order", random_seed, random_seed);
+        }
+    }
+    let (manifest, count) = match &args.command {
+        Command::Test(test_args) => (test_args.manifest.as_deref(), test_args.count),
+        _ => (None, false),
+    };
+    let reads_robots = args.robots_text_file_path.is_some() || args.robots_url.is_some() || args.robots_inline.is_some();
+    let composes = matches!(args.command, Command::Compose(_));
+    match (reads_robots, manifest) {
+        (true, _) if composes => Args::command()
+            .error(ErrorKind::ArgumentConflict, "compose reads the robots.txt from its fragments, it can't be used with --robots-text-file-path, --robots-url, --site or --robots-inline")
+            .exit(),
+        // Counting never reads the robots.txt
+        (false, None) if !count && !composes => Args::command()
+            .error(ErrorKind::MissingRequiredArgument, "one of --robots-text-file-path, --robots-url, --site or --robots-inline is required")
+            .exit(),
+        (true, Some(_)) => Args::command()
+            .error(ErrorKind::ArgumentConflict, "--manifest can't be used with --robots-text-file-path, --robots-url, --site or --robots-inline")
+            .exit(),
+        _ => {},
+    }
+
+    let test_case_file_path = match &args.command {
+        Command::Test(test_args) => test_args.test_case_file_path.as_deref(),
+        Command::Compare { test_case_file_path, .. } => Some(test_case_file_path.as_str()),
+        Command::Bench(bench_args) => bench_args.test_case_file_path.as_deref(),
+        Command::Classify { url_list, .. } => Some(url_list.as_str()),
+        _ => None,
+    };
+    if args.robots_text_file_path.as_deref() == Some(STDIN_PATH) && test_case_file_path == Some(STDIN_PATH) {
+        eprintln!("error: only one of --robots-text-file-path and --test-case-file-path can read from stdin");
+        return ExitStatus::Usage.into();
+    }
+    if let Command::Compose(compose_args) = &args.command {
+        let stdin_readers = compose_args.fragments.iter()
+            .map(String::as_str)
+            .chain(compose_args.test_case_file_path.as_deref())
+            .filter(|&path| path == STDIN_PATH)
+            .count();
+        if stdin_readers > 1 {
+            eprintln!("error: only one of the fragments and --test-case-file-path can read from stdin");
+            return ExitStatus::Usage.into();
+        }
+    }
+
+    if let Command::Test(test_args) = &args.command {
+        if test_args.watch {
+            return watch(&args, test_args);
+        }
+        if let Some(timeout) = test_args.timeout {
+            return run_with_timeout(&args, Duration::from_secs(timeout));
+        }
+    }
+    run(&args)
+}
+
+/// Parse the command line, taking the defaults of options it leaves out from the config file
+///
+/// Environment variables still take precedence over the config file.
+fn parse_args() -> Result<Args, RunError> {
+    // The config has to be found before the real parse, as it may supply required options
+    let config_path = Args::command()
+        .ignore_errors(true)
+        .get_matches()
+        .get_one::<String>("config")
+        .cloned()
+        .or_else(|| Some(CONFIG_FILE_NAME.to_string()).filter(|path| Path::new(path).is_file()));
+    let Some(config_path) = config_path else {
+        return Ok(Args::parse());
+    };
+
+    let config = load_config(Path::new(&config_path)).map_err(|e| {
+        RunError::new(ExitStatus::of_input_error(e.as_ref()), format!("error reading config file {}: {}", config_path, e))
+    })?;
+    let command = apply_config(Args::command(), &config)
+        .map_err(|e| RunError::new(ExitStatus::Usage, format!("error reading config file {}: {}", config_path, e)))?;
+    let matches = command.get_matches();
+    let mut args = Args::from_arg_matches(&matches).unwrap_or_else(|e| e.exit());
+    // The config's robots.txt gives way to any other source given
+    let robots_path_from_config = matches!(matches.value_source("robots_text_file_path"), Some(ValueSource::DefaultValue));
+    let manifest = matches!(&args.command, Command::Test(TestArgs { manifest: Some(_), .. }));
+    let composes = matches!(&args.command, Command::Compose(_));
+    if robots_path_from_config && (args.robots_url.is_some() || args.site.is_some() || args.robots_inline.is_some() || manifest || composes) {
+        args.robots_text_file_path = None;
+    }
+    Ok(args)
+}
+
+/// Use the config's values as the defaults of the options they set
+fn apply_config(command: clap::Command, config: &Config) -> Result<clap::Command, String> {
+    if let Some(output_format) = &config.output_format {
+        parse_output_spec(output_format).map_err(|e| format!("invalid output_format, {}", e))?;
+    }
+    if let Some(fail_under) = config.fail_under {
+        parse_percentage(&fail_under.to_string()).map_err(|e| format!("invalid fail_under, {}", e))?;
+    }
+
+    let mut command = command;
+    if let Some(path) = &config.robots_text_file_path {
+        command = command.mut_arg("robots_text_file_path", |arg| arg.default_value(path.clone()));
+    }
+    Ok(command.mut_subcommand("test", |test| {
+        let mut test = test;
+        if let Some(path) = &config.test_case_file_path {
+            // A default doesn't count as given, so the option is no longer required
+            test = test.mut_arg("test_case_file_path", |arg| arg.default_value(path.clone()).required_unless_present(Resettable::Reset));
+        }
+        if let Some(output_format) = &config.output_format {
+            test = test.mut_arg("output_format", |arg| arg.default_value(output_format.clone()));
+        }
+        if let Some(generate_test_report) = config.generate_test_report {
+            test = test.mut_arg("generate_test_report", |arg| arg.default_value(generate_test_report.to_string()));
+        }
+        if let Some(fail_under) = config.fail_under {
+            test = test.mut_arg("fail_under", |arg| arg.default_value(fail_under.to_string()));
+        }
+        test
+    }))
+}
+
+/// Run on a worker thread, exiting with [`TIMEOUT_EXIT_CODE`] if it hasn't finished in time
+fn run_with_timeout(args: &Args, timeout: Duration) -> ExitCode {
+    let (sender, receiver) = mpsc::channel();
+    let worker_args = args.clone();
+    thread::spawn(move || {
+        let _ = sender.send(run(&worker_args));
+    });
+
+    match receiver.recv_timeout(timeout) {
+        Ok(exit_code) => exit_code,
+        Err(RecvTimeoutError::Timeout) => {
+            eprintln!(
+                "error: timed out after {}s, {} test cases were evaluated",
+                timeout.as_secs(),
+                TEST_CASES_COMPLETED.load(Ordering::Relaxed)
+            );
+            ExitCode::from(TIMEOUT_EXIT_CODE)
+        },
+        // The worker panicked, so exit the way an uncaught panic does
+        Err(RecvTimeoutError::Disconnected) => ExitCode::from(101),
+    }
+}
+
+/// Run the requested subcommand once, on a thread pool of its own when --jobs is given
+fn run(args: &Args) -> ExitCode {
+    let Command::Test(TestArgs { jobs: Some(jobs), .. }) = &args.command else {
+        return run_subcommand(args);
+    };
+    match rayon::ThreadPoolBuilder::new().num_threads(jobs.get()).build() {
+        Ok(pool) => pool.install(|| run_subcommand(args)),
+        Err(e) => {
+            eprintln!("error starting {} threads: {}", jobs, e);
+            ExitStatus::Usage.into()
+        },
+    }
+}
+
+/// A seed for --shuffle that differs between runs
+fn random_seed() -> u64 {
+    // The standard library's hasher is randomly keyed for each process
+    let mut hasher = RandomState::new().build_hasher();
+    hasher.write_u128(SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |elapsed| elapsed.as_nanos()));
+    hasher.finish()
+}
+
+/// Print logs to stderr, keeping stdout for results
+///
+/// RUST_LOG picks the logs when set, otherwise --quiet leaves only errors, and each --verbose
+/// adds a level below the default of info. Other crates only log their warnings. Spans log how
+/// long they took as they close, so fetching, parsing and evaluating are timed from -v on.
+fn init_logging(args: &Args) {
+    let level = match (args.quiet, args.verbose) {
+        (true, _) => "error",
+        (false, 0) => "info",
+        (false, 1) => "debug",
+        (false, _) => "trace",
+    };
+    // Other crates' logs are only worth the noise when something goes wrong
+    let filter = EnvFilter::try_from_default_env()
+        .unwrap_or_else(|_| EnvFilter::new(format!("warn,{}={}", env!("CARGO_CRATE_NAME"), level)));
+    tracing_subscriber::fmt()
+        .with_env_filter(filter)
+        .with_writer(io::stderr)
+        .with_ansi(io::stderr().is_terminal())
+        .with_target(false)
+        .with_span_events(FmtSpan::CLOSE)
+        // No timestamps, unlike without_time() which drops the spans' timings too
+        .with_timer(())
+        .init();
+}
+
+fn run_subcommand(args: &Args) -> ExitCode {
+    let start = Instant::now();
+    // --watch runs again in the same process, so each run counts its own failures
+    TEST_CASES_FAILED.store(0, Ordering::Relaxed);
+
+    if let Command::Test(test_args @ TestArgs { count: true, .. }) = &args.command {
+        return count_test_cases(args, test_args);
+    }
+    if let Command::Test(test_args @ TestArgs { manifest: Some(manifest_path), .. }) = &args.command {
+        return match run_manifest(manifest_path, args, test_args) {
+            Ok((suites, test_results)) => report_results(args, test_args, start, manifest_path, &get_file_stem(manifest_path), &suites, &test_results),
+            Err(e) => e.report(),
+        };
+    }
+    if let Command::Compose(compose_args) = &args.command {
+        return run_compose(args, compose_args);
+    }
+
+    let retry = RetryPolicy { retries: args.retries, delay: Duration::from_millis(args.retry_delay) };
+    let log_retry = |message: &str| debug!("{}", message);
+    let (robots_source, mut robots_content) = match (&args.robots_inline, &args.robots_text_file_path, &args.robots_url) {
+        // A shell quoting a single line leaves \n as it is, so it stands for a line break too
+        (Some(content), _, _) => (INLINE_ROBOTS_SOURCE, content.replace("\\n", "\n").into_bytes()),
+        (None, Some(path), _) => match read_robots_file(path) {
+            Ok(robots_content) => (path.as_str(), robots_content),
+            Err(e) => {
+                eprintln!("error reading robots.txt file {}: {}", path, e);
+                return ExitStatus::of_input_error(&e).into();
+            },
+        },
+        (None, None, Some(url)) => match debug_span!("fetch", url).in_scope(|| fetch_robots_cached(url, args, &retry, log_retry)) {
+            Ok(content) => match decompress_robots(content, None) {
+                Ok(robots_content) => (url.as_str(), robots_content),
+                Err(e) => {
+                    eprintln!("error decompressing robots.txt from {}: {}", url, e);
+                    return ExitStatus::Parse.into();
+                },
+            },
+            Err(e) => {
+                eprintln!("error fetching robots.txt: {}", e);
+                return ExitStatus::Network.into();
+            },
+        },
+        (None, None, None) => unreachable!("main requires one robots.txt source"),
+    };
+    if let Err(e) = check_robots_content(robots_source, &mut robots_content, args) {
+        return e.report();
+    }
+    if let Some(path) = &args.print_effective_robots {
+        if let Err(e) = print_effective_robots(robots_source, &robots_content, path.as_deref()) {
+            eprintln!("error writing the effective robots.txt: {}", e);
+            return ExitStatus::Io.into();
+        }
+    }
+
+    match &args.command {
+        Command::Test(test_args) => run_test_cases(args, test_args, start, robots_source, &robots_content),
+        Command::Lint { check_conflicts } => run_lint(&robots_content, args, *check_conflicts),
+        Command::Check { user_agent, url, explain: true, explain_format } => run_explain(&robots_content, robots_source, args, url, user_agent, *explain_format),
+        Command::Check { user_agent, url, explain: false, .. } => run_check(&robots_content, robots_source, args, url, user_agent),
+        Command::Explain { url, user_agent, explain_format } => run_explain(&robots_content, robots_source, args, url, user_agent, *explain_format),
+        Command::ExplainPattern { path, user_agent } => run_explain_pattern(&robots_content, robots_source, args, path, user_agent),
+        Command::Sitemaps => list_sitemaps(&robots_content, robots_source, args),
+        Command::Hash { expected_hash, canonical } => run_hash(&robots_content, args, expected_hash.as_deref(), *canonical),
+        Command::Bench(bench_args) => run_bench(&robots_content, robots_source, args, bench_args),
+        Command::Compose(_) => unreachable!("compose reads its robots.txt from the fragments"),
+        Command::Classify { url_list, user_agent, classify_format } => {
+            run_classify(&robots_content, robots_source, args, url_list, user_agent, *classify_format)
+        },
+        Command::Compare { baseline_robots_path, test_case_file_path, loading } => {
+            run_compare(&robots_content, baseline_robots_path, test_case_file_path, args, loading)
+        },
+    }
+}
+
+/// Print the test cases decided differently by the baseline robots.txt, failing if there are any
+fn run_compare(robots_content: &[u8], baseline_robots_path: &str, test_case_file_path: &str, args: &Args, loading: &TestCaseLoadingArgs) -> ExitCode {
+    let mut baseline_content = match read_robots_file(baseline_robots_path) {
+        Ok(baseline_content) => baseline_content,
+        Err(e) => {
+            eprintln!("error reading robots.txt file {}: {}", baseline_robots_path, e);
+            return ExitStatus::of_input_error(&e).into();
+        },
+    };
+    if let Err(e) = check_robots_content(baseline_robots_path, &mut baseline_content, args) {
+        return e.report();
+    }
+    let test_cases = match get_test_cases(test_case_file_path, args, loading) {
+        Ok(test_case_file) => test_case_file.test_cases,
+        Err(e) => {
+            eprintln!("error getting test cases from {}: {}", test_case_file_path, e);
+            return ExitStatus::of_input_error(e.as_ref()).into();
+        },
+    };
+    let changes = match compare_decisions(&baseline_content, robots_content, &test_cases) {
+        Ok(changes) => changes,
+        Err(e) => {
+            eprintln!("error parsing robots.txt: {}", e);
+            return ExitStatus::Parse.into();
+        },
+    };
+
+    let output_result = write_output(args, |output| {
+        for change in &changes {
+            writeln!(
+                output,
+                "{} {} was={} now={}",
+                change.user_agent,
+                change.url,
+                decision_label(change.baseline_allowed),
+                decision_label(change.allowed)
+            )?;
+        }
+        writeln!(output, "Changed decisions: {}", changes.len())?;
+        Ok(())
+    });
+    if let Err(e) = output_result {
+        eprintln!("error writing results: {}", e);
+        return ExitStatus::Io.into();
+    }
+    if changes.is_empty() { ExitCode::SUCCESS } else { ExitStatus::TestFailure.into() }
+}
+
+/// Run the test case files against the robots.txt and report the results
+fn run_test_cases(args: &Args, test_args: &TestArgs, start: Instant, robots_source: &str, robots_content: &[u8]) -> ExitCode {
+    if test_args.self_test {
+        return run_self_test(args, test_args, start, robots_source, robots_content);
+    }
+    let Some(test_case_file_path) = test_args.test_case_file_path.as_deref() else {
+        unreachable!("clap requires a test case file without a manifest or --self-test")
+    };
+
+    let test_case_file_paths = match expand_test_case_file_paths(test_case_file_path) {
+        Ok(test_case_file_paths) => test_case_file_paths,
+        Err(e) => {
+            eprintln!("error getting test cases from {}: {}", test_case_file_path, e);
+            return ExitStatus::of_input_error(e.as_ref()).into();
+        },
+    };
+
+    if test_args.explain_all {
+        return explain_user_agents(args, test_args, &test_case_file_paths, robots_source, robots_content);
+    }
+
+    // Each matched file is its own suite, named after it
+    let robots: Arc<str> = String::from_utf8_lossy(robots_content).into();
+    let mut suites = Vec::new();
+    let mut test_results = Vec::new();
+    for path in test_case_file_paths {
+        let suite_start = test_results.len();
+        let (test_run, description) = match run_suite(robots_source, robots_content, &path, args, test_args) {
+            Ok(suite_run) => suite_run,
+            Err(e) => return e.report(),
+        };
+        test_results.extend(test_run.results);
+        suites.push(Suite {
+            name: get_file_stem(&path),
+            description,
+            test_case_file_path: path,
+            results: suite_start..test_results.len(),
+            robots: Arc::clone(&robots),
+            summary: test_run.summary,
+            stopped_early: test_run.stopped_early,
+        });
+    }
+
+    // A pattern has no meaningful stem, so its report is named after the robots.txt instead
+    let report_name = if is_glob_pattern(test_case_file_path) {
+        get_file_stem(robots_source)
+    } else {
+        get_file_stem(test_case_file_path)
+    };
+    report_results(args, test_args, start, robots_source, &report_name, &suites, &test_results)
+}
+
+/// Run the test cases generated from the robots.txt's rules, as a suite named after it
+fn run_self_test(args: &Args, test_args: &TestArgs, start: Instant, robots_source: &str, robots_content: &[u8]) -> ExitCode {
+    let robots: Arc<str> = String::from_utf8_lossy(robots_content).into();
+    let test_cases = generate_test_cases(&robots);
+    let test_run = match run_suite_cases(robots_source, robots_content, &test_cases, args, test_args) {
+        Ok(test_run) => test_run,
+        Err(e) => return e.report(),
+    };
+
+    let name = get_file_stem(robots_source);
+    // The line numbers are of the rules, so annotations point at the robots.txt
+    let suites = [Suite {
+        name: name.clone(),
+        description: None,
+        test_case_file_path: robots_source.to_string(),
+        results: 0..test_run.results.len(),
+        robots,
+        summary: test_run.summary,
+        stopped_early: test_run.stopped_early,
+    }];
+    report_results(args, test_args, start, robots_source, &name, &suites, &test_run.results)
+}
+
+/// Print the rules applying to every user agent of the test case files, and the decision for the
+/// root path
+fn explain_user_agents(args: &Args, test_args: &TestArgs, test_case_file_paths: &[String], robots_source: &str, robots_content: &[u8]) -> ExitCode {
+    let mut user_agents = BTreeSet::new();
+    for path in test_case_file_paths {
+        let test_cases = match get_test_cases(path, args, &test_args.loading) {
+            Ok(test_case_file) => test_case_file.test_cases,
+            Err(e) => {
+                eprintln!("error getting test cases from {}: {}", path, e);
+                return ExitStatus::of_input_error(e.as_ref()).into();
+            },
+        };
+        user_agents.extend(test_cases.into_iter()
+            .filter(|test| !test.is_sitemap_assertion() && !test.is_host_assertion())
+            .map(|test| test.user_agent));
+    }
+
+    let robots = String::from_utf8_lossy(robots_content);
+    let mut explanations = Vec::new();
+    for user_agent in &user_agents {
+        match explain(&robots, user_agent, "/") {
+            Ok(explanation) => explanations.push((user_agent.as_str(), explanation)),
+            Err(e) => {
+                eprintln!("error parsing robots.txt {}: {}", robots_source, e);
+                return ExitStatus::Parse.into();
+            },
+        }
+    }
+
+    // Only one format makes sense for a listing, so it is the first one given
+    let output_format = test_args.output_format.first().map_or(OutputFormat::Text, |spec| spec.format);
+    let output_result = write_output(args, |output| match output_format {
+        OutputFormat::Json | OutputFormat::Ndjson => write_json_agent_rules(output, &explanations),
+        _ => {
+            for (user_agent, explanation) in &explanations {
+                writeln!(output, "User-agent: {}", user_agent)?;
+                writeln!(output, "  Root path /: {}", decision_label(explanation.allowed))?;
+                writeln!(output, "  Crawl-delay: {}", crawl_delay_label(explanation.crawl_delay))?;
+                writeln!(output, "  Rules for User-agent: {}", explanation.group_user_agent)?;
+                for rule in &explanation.rules {
+                    writeln!(output, "    line {}: {}: {}", rule.line_number, rule.key, rule.value)?;
+                }
+            }
+            Ok(())
+        },
+    });
+    if let Err(e) = output_result {
+        eprintln!("error writing results: {}", e);
+        return ExitStatus::Io.into();
+    }
+    ExitCode::SUCCESS
+}
+
+/// Load every test case file and print the number of test cases, in total and per user agent
+///
+/// Any file failing to load is an error, so this doubles as a check of the test case files.
+fn count_test_cases(args: &Args, test_args: &TestArgs) -> ExitCode {
+    let test_case_file_paths = match (&test_args.manifest, &test_args.test_case_file_path) {
+        (Some(manifest_path), _) => match load_manifest(Path::new(manifest_path)) {
+            Ok(entries) => entries.iter().map(|entry| entry.test_case_path.display().to_string()).collect(),
+            Err(e) => {
+                eprintln!("error reading manifest {}: {}", manifest_path, e);
+                return ExitStatus::of_input_error(e.as_ref()).into();
+            },
+        },
+        (None, Some(pattern)) => match expand_test_case_file_paths(pattern) {
+            Ok(test_case_file_paths) => test_case_file_paths,
+            Err(e) => {
+                eprintln!("error getting test cases from {}: {}", pattern, e);
+                return ExitStatus::of_input_error(e.as_ref()).into();
+            },
+        },
+        (None, None) => unreachable!("clap requires a test case file without a manifest"),
+    };
+
+    let mut total = 0;
+    let mut user_agents: BTreeMap<String, usize> = BTreeMap::new();
+    for path in test_case_file_paths {
+        let test_cases = match get_test_cases(&path, args, &test_args.loading) {
+            Ok(test_case_file) => test_case_file.test_cases,
+            Err(e) => {
+                eprintln!("error getting test cases from {}: {}", path, e);
+                return ExitStatus::of_input_error(e.as_ref()).into();
+            },
+        };
+        total += test_cases.len();
+        for test_case in test_cases {
+            *user_agents.entry(test_case.user_agent).or_default() += 1;
+        }
+    }
+
+    let output_result = write_output(args, |output| {
+        writeln!(output, "Test cases: {}", total)?;
+        for (user_agent, count) in &user_agents {
+            writeln!(output, "  {}: {}", user_agent, count)?;
+        }
+        Ok(())
+    });
+    if let Err(e) = output_result {
+        eprintln!("error writing results: {}", e);
+        return ExitStatus::Io.into();
+    }
+    ExitCode::SUCCESS
+}
+
+/// Expand a test case file glob pattern such as `tests/*.csv`, other paths are kept as they are
+fn expand_test_case_file_paths(pattern: &str) -> Result<Vec<String>, Box<dyn Error>> {
+    if !is_glob_pattern(pattern) {
+        return Ok(vec![pattern.to_string()]);
+    }
+
+    let paths = glob::glob(pattern)?
+        .map(|path| path.map(|path| path.display().to_string()))
+        .collect::<Result<Vec<String>, _>>()?;
+    if paths.is_empty() {
+        return Err("the pattern matched no files".into());
+    }
+    Ok(paths)
+}
+
+fn is_glob_pattern(path: &str) -> bool {
+    path.contains(['*', '?', '['])
+}
+
+/// Delay used to collapse the bursts of events a single save produces
+const WATCH_DEBOUNCE: Duration = Duration::from_millis(200);
+
+enum WatchMessage {
+    Changed(notify::Result<notify::Event>),
+    Interrupted,
+}
+
+/// Run, then clear the screen and run again each time one of the input files changes
+fn watch(args: &Args, test_args: &TestArgs) -> ExitCode {
+    let mut watched_paths = Vec::new();
+    if let Some(path) = args.robots_text_file_path.as_deref() {
+        watched_paths.push(path.to_string());
+    }
+    if let Some(pattern) = test_args.test_case_file_path.as_deref() {
+        match expand_test_case_file_paths(pattern) {
+            Ok(paths) => watched_paths.extend(paths),
+            Err(e) => {
+                eprintln!("error getting test cases from {}: {}", pattern, e);
+                return ExitStatus::of_input_error(e.as_ref()).into();
+            },
+        }
+    }
+    if watched_paths.iter().any(|path| path == STDIN_PATH) {
+        eprintln!("error: --watch can't be used when reading from stdin");
+        return ExitStatus::Usage.into();
+    }
+    // Editors often replace a file rather than write to it, so watch the directories holding them
+    let watched_paths: Vec<PathBuf> = watched_paths.iter()
+        .map(|path| fs::canonicalize(path).unwrap_or_else(|_| PathBuf::from(path)))
+        .collect();
+
+    let (sender, receiver) = mpsc::channel();
+    let interrupt_sender = sender.clone();
+    if let Err(e) = ctrlc::set_handler(move || {
+        let _ = interrupt_sender.send(WatchMessage::Interrupted);
+    }) {
+        eprintln!("error watching for changes: {}", e);
+        return ExitStatus::Io.into();
+    }
+    let mut watcher = match notify::recommended_watcher(move |event| {
+        let _ = sender.send(WatchMessage::Changed(event));
+    }) {
+        Ok(watcher) => watcher,
+        Err(e) => {
+            eprintln!("error watching for changes: {}", e);
+            return ExitStatus::Io.into();
+        },
+    };
+    // Files of the same directory need not be next to each other
+    let watched_directories: BTreeSet<&Path> = watched_paths.iter()
+        .map(|path| path.parent().unwrap_or_else(|| Path::new(".")))
+        .collect();
+    for directory in watched_directories {
+        if let Err(e) = watcher.watch(directory, RecursiveMode::NonRecursive) {
+            eprintln!("error watching {}: {}", directory.display(), e);
+            return ExitStatus::Io.into();
+        }
+    }
+
+    loop {
+        if !args.quiet {
+            print!("\x1b[2J\x1b[H");
+        }
+        run(args);
+        if !args.quiet {
+            println!("Watching for changes, press Ctrl-C to stop");
+        }
+
+        // Wait for a change to one of the watched files, then for the events to settle
+        loop {
+            match receiver.recv() {
+                Ok(WatchMessage::Changed(Ok(event))) if !event.kind.is_access() && event.paths.iter().any(|path| watched_paths.contains(path)) => break,
+                Ok(WatchMessage::Changed(Ok(_))) => {},
+                Ok(WatchMessage::Changed(Err(e))) => eprintln!("error watching for changes: {}", e),
+                Ok(WatchMessage::Interrupted) | Err(_) => return ExitCode::SUCCESS,
+            }
+        }
+        loop {
+            match receiver.recv_timeout(WATCH_DEBOUNCE) {
+                Ok(WatchMessage::Changed(_)) => {},
+                Ok(WatchMessage::Interrupted) => return ExitCode::SUCCESS,
+                Err(RecvTimeoutError::Timeout) => break,
+                Err(RecvTimeoutError::Disconnected) => return ExitCode::SUCCESS,
+            }
+        }
+    }
+}
+
+/// Run every robots.txt and test case file pair listed in a manifest, in order
+fn run_manifest(manifest_path: &str, args: &Args, test_args: &TestArgs) -> Result<(Vec<Suite>, Vec<TestCaseOutput>), RunError> {
+    let entries = load_manifest(Path::new(manifest_path)).map_err(|e| {
+        RunError::new(ExitStatus::of_input_error(e.as_ref()), format!("error reading manifest {}: {}", manifest_path, e))
+    })?;
+
+    if args.print_effective_robots.is_some() {
+        warn!("--print-effective-robots isn't used with --manifest");
+    }
+    let mut suites = Vec::new();
+    let mut test_results = Vec::new();
+    for entry in entries {
+        let robots_path = entry.robots_path.display().to_string();
+        let test_case_file_path = entry.test_case_path.display().to_string();
+        let mut robots_content = read_robots_file(&robots_path).map_err(|e| {
+            RunError::new(ExitStatus::of_input_error(&e), format!("error reading robots.txt file {}: {}", robots_path, e))
+        })?;
+        check_robots_content(&robots_path, &mut robots_content, args)?;
+
+        let suite_start = test_results.len();
+        let (test_run, description) = run_suite(&robots_path, &robots_content, &test_case_file_path, args, test_args)?;
+        test_results.extend(test_run.results);
+        suites.push(Suite {
+            name: robots_path,
+            description,
+            test_case_file_path,
+            results: suite_start..test_results.len(),
+            robots: String::from_utf8_lossy(&robots_content).into(),
+            summary: test_run.summary,
+            stopped_early: test_run.stopped_early,
+        });
+    }
+    Ok((suites, test_results))
+}
+
+/// Warn about content the matcher may not read as intended: a byte order mark, which is stripped,
+/// invalid UTF-8 and, when the robots.txt is over Google's size limit, truncating it if asked to
+///
+/// Under --strict an oversized robots.txt is an error instead.
+fn check_robots_content(robots_source: &str, robots_content: &mut Vec<u8>, args: &Args) -> Result<(), RunError> {
+    if strip_bom(robots_content) {
+        warn!("robots.txt {} starts with a byte order mark, it was removed", robots_source);
+    }
+    // Robot::new takes bytes, so invalid UTF-8 doesn't stop the run but may not match as expected
+    if let Some(line) = first_invalid_utf8_line(robots_content) {
+        warn!("robots.txt {} line {}: invalid UTF-8", robots_source, line);
+    }
+
+    if robots_content.len() <= GOOGLE_SIZE_LIMIT {
+        return Ok(());
+    }
+
+    let problem = format!(
+        "robots.txt {} is {} bytes, larger than the {} bytes (500 KiB) Google parses",
+        robots_source,
+        robots_content.len(),
+        GOOGLE_SIZE_LIMIT
+    );
+    if args.strict {
+        return Err(RunError::new(ExitStatus::TestFailure, format!("error: {}", problem)));
+    }
+    if args.truncate_to_limit {
+        warn!("{}, only the first {} bytes are tested", problem, GOOGLE_SIZE_LIMIT);
+        robots_content.truncate(GOOGLE_SIZE_LIMIT);
+    } else {
+        warn!("{}", problem);
+    }
+    Ok(())
+}
+
+/// Run a test case file, returning the results and the file's description
+fn run_suite(robots_source: &str, robots_content: &[u8], test_case_file_path: &str, args: &Args, test_args: &TestArgs) -> Result<(TestRun, Option<String>), RunError> {
+    let test_case_file = get_test_cases(test_case_file_path, args, &test_args.loading).map_err(|e| {
+        RunError::new(ExitStatus::of_input_error(e.as_ref()), format!("error getting test cases from {}: {}", test_case_file_path, e))
+    })?;
+    // An empty file among several is as likely a broken pipeline as an empty run
+    if test_case_file.test_cases.is_empty() && !test_args.allow_empty {
+        return Err(RunError::new(
+            ExitStatus::Usage,
+            format!("error: test case file {} has no test cases, pass --allow-empty to accept this", test_case_file_path),
+        ));
+    }
+    let test_run = run_suite_cases(robots_source, robots_content, &test_case_file.test_cases, args, test_args)?;
+    Ok((test_run, test_case_file.description))
+}
+
+/// Run already loaded test cases, warning about what the options ask to check first
+fn run_suite_cases(robots_source: &str, robots_content: &[u8], test_cases: &[TestCaseDefinition], args: &Args, test_args: &TestArgs) -> Result<TestRun, RunError> {
+    let robots = String::from_utf8_lossy(robots_content);
+    if !test_args.case_insensitive_agent {
+        for warning in check_user_agent_case(&robots, test_cases.iter().map(|test| test.user_agent.as_str())) {
+            warn!("robots.txt {} {}", robots_source, warning);
+        }
+    }
+    for user_agent in undeclared_user_agents(&robots, test_cases.iter().map(|test| test.user_agent.as_str())) {
+        warn!("robots.txt {} has no group for user agent {}, so its test cases get the rules for *", robots_source, user_agent);
+    }
+    check_test_case_hosts(test_cases, args)?;
+    if test_args.require_all_agents_covered {
+        check_agents_covered(robots_source, robots_content, test_cases)?;
+    }
+    if test_args.strict_trailing_slash {
+        check_trailing_slashes(robots_source, robots_content, test_cases)?;
+    }
+    #[cfg(feature = "compare-parsers")]
+    if test_args.compare_parsers {
+        check_parsers_agree(robots_source, robots_content, test_cases)?;
+    }
+    if test_args.show_host {
+        for warning in check_hosts(&String::from_utf8_lossy(robots_content)) {
+            warn!("robots.txt {} {}", robots_source, warning);
+        }
+    }
+    let reordered_test_cases: Vec<TestCaseDefinition>;
+    let test_cases = if test_args.invert || test_args.shuffle.is_some() {
+        reordered_test_cases = {
+            let mut test_cases = test_cases.to_vec();
+            if test_args.invert {
+                for test in &mut test_cases {
+                    test.expected_result = test.expected_result != test.checks_url();
+                }
+            }
+            if let Some(seed) = test_args.shuffle.flatten() {
+                shuffle_test_cases(&mut test_cases, seed);
+            }
+            test_cases
+        };
+        &reordered_test_cases
+    } else {
+        test_cases
+    };
+    let show_progress = !test_args.no_progress && !args.quiet && test_args.outputs_text(args) && io::stderr().is_terminal();
+    let max_failures = test_args.max_failures.map_or(usize::MAX, NonZeroUsize::get);
+    let test_run = run_tests_with_progress_bar(robots_content, test_cases, show_progress, max_failures);
 
-    /// test cases file content path
-    #[arg(short, long)]
-    test_case_file_path: String,
-    
-    /// generate test report
-    #[arg(short, long, default_value_t = false)]
-    generate_test_report: bool,
+    // Each user agent's parse error once, rather than for each of its test cases
+    let mut errored_by_agent: BTreeMap<&str, (&str, usize)> = BTreeMap::new();
+    for result in &test_run.results {
+        if let Some(error) = &result.error {
+            errored_by_agent.entry(&result.user_agent).or_insert((error, 0)).1 += 1;
+        }
+    }
+    for (error, count) in errored_by_agent.values() {
+        eprintln!("error: robots.txt {}: {} ({} test cases errored)", robots_source, error, count);
+    }
+    Ok(test_run)
 }
 
-fn main() -> ExitCode {
-    let start = std::time::Instant::now();
-    let args = Args::parse();
-    let robots_content = fs::read_to_string(args.robots_text_file_path).expect("Unable to read robots.txt file");
+/// Warn about absolute test case URLs on another host than the fetched robots.txt, which it
+/// doesn't apply to, failing under --strict
+///
+/// Bare paths are on the robots.txt's own host, as is anything when it was read from a file.
+fn check_test_case_hosts(test_cases: &[TestCaseDefinition], args: &Args) -> Result<(), RunError> {
+    let Some(robots_url) = args.robots_url.as_deref().and_then(|robots_url| Url::parse(robots_url).ok()) else {
+        return Ok(());
+    };
+    let Some(expected_host) = robots_url.host_str() else { return Ok(()) };
+
+    for (index, test) in test_cases.iter().enumerate() {
+        let Some(host) = Url::parse(&test.url).ok().and_then(|url| url.host_str().map(str::to_string)) else { continue };
+        if host == expected_host {
+            continue;
+        }
+        let position = match test.line_number {
+            Some(line_number) => format!("line {}", line_number),
+            None => format!("test case {}", index + 1),
+        };
+        let problem = format!("{}: {} is on host {}, not {} the robots.txt applies to", position, test.url, host, expected_host);
+        if args.strict {
+            return Err(RunError::new(ExitStatus::TestFailure, format!("error: {}", problem)));
+        }
+        warn!("{}", problem);
+    }
+    Ok(())
+}
 
-    let r = Robot::new("googlebot", robots_content.as_bytes()).unwrap();
+/// Fail if the robotstxt crate decides any test case URL differently, printing both decisions
+#[cfg(feature = "compare-parsers")]
+fn check_parsers_agree(robots_source: &str, robots_content: &[u8], test_cases: &[TestCaseDefinition]) -> Result<(), RunError> {
+    let disagreements = parser_disagreements(robots_content, test_cases)
+        .map_err(|e| RunError::new(ExitStatus::Parse, format!("error parsing robots.txt {}: {}", robots_source, e)))?;
+    if disagreements.is_empty() {
+        return Ok(());
+    }
 
-    let test_cases = match get_test_cases(&args.test_case_file_path) {
-        Ok(test_cases)  => test_cases,
-        Err(e) => {
-            println!("error getting test cases: {}", e);
-            return ExitCode::FAILURE;
-        },
-    };
+    for disagreement in &disagreements {
+        let position = disagreement.line_number.map_or_else(String::new, |line_number| format!("line {}: ", line_number));
+        eprintln!(
+            "error: {}{} for {}: texting_robots={} robotstxt={}",
+            position,
+            disagreement.url,
+            disagreement.user_agent,
+            decision_label(disagreement.allowed),
+            decision_label(disagreement.robotstxt_allowed)
+        );
+    }
+    Err(RunError::new(
+        ExitStatus::TestFailure,
+        format!("error: texting_robots and robotstxt decide {} test case URLs differently for robots.txt {}", disagreements.len(), robots_source),
+    ))
+}
+
+/// Fail if any user agent the robots.txt names a group for has no test cases, printing each of them
+fn check_agents_covered(robots_source: &str, robots_content: &[u8], test_cases: &[TestCaseDefinition]) -> Result<(), RunError> {
+    let robots = String::from_utf8_lossy(robots_content);
+    let uncovered = uncovered_user_agents(&robots, test_cases.iter().map(|test| test.user_agent.as_str()));
+    if uncovered.is_empty() {
+        return Ok(());
+    }
+
+    for line in &uncovered {
+        eprintln!("error: robots.txt {} line {}: User-agent {} has no test cases", robots_source, line.line_number, line.value);
+    }
+    Err(RunError::new(
+        ExitStatus::TestFailure,
+        format!("error: {} user agents named in robots.txt {} have no test cases", uncovered.len(), robots_source),
+    ))
+}
+
+/// Fail if any test case URL is decided differently with and without a trailing slash, printing
+/// each of them
+fn check_trailing_slashes(robots_source: &str, robots_content: &[u8], test_cases: &[TestCaseDefinition]) -> Result<(), RunError> {
+    let ambiguities = trailing_slash_ambiguities(robots_content, test_cases)
+        .map_err(|e| RunError::new(ExitStatus::Parse, format!("error parsing robots.txt {}: {}", robots_source, e)))?;
+    if ambiguities.is_empty() {
+        return Ok(());
+    }
 
-    let test_results: Vec<TestCaseOutput> = test_cases.par_iter()
-        .map(|test| {
-            let matcher_result = r.allowed(&test.url);
-            // println!("Expected result: {}, result: {}", test.expected_result, matcher_result);
-            TestCaseOutput {
-                result: matcher_result == test.expected_result,
-                expected_result: test.expected_result,
-                url: &test.url,
-                user_agent: &test.user_agent
+    for ambiguity in &ambiguities {
+        let position = ambiguity.line_number.map_or_else(String::new, |line_number| format!("line {}: ", line_number));
+        eprintln!(
+            "error: {}{} is {} for {} but {} is {}",
+            position,
+            ambiguity.url,
+            decision_label(ambiguity.allowed),
+            ambiguity.user_agent,
+            ambiguity.variant_url,
+            decision_label(!ambiguity.allowed)
+        );
+    }
+    Err(RunError::new(
+        ExitStatus::TestFailure,
+        format!("error: {} test case URLs depend on a trailing slash in robots.txt {}", ambiguities.len(), robots_source),
+    ))
+}
+
+/// How often the progress bar is redrawn
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Run the test cases, drawing a progress bar on stderr as they complete when `show_progress`
+fn run_tests_with_progress_bar(robots_content: &[u8], test_cases: &[TestCaseDefinition], show_progress: bool, max_failures: usize) -> TestRun {
+    let run = || run_tests_with_max_failures(robots_content, test_cases, &TEST_CASES_COMPLETED, &TEST_CASES_FAILED, max_failures);
+    if !show_progress {
+        return run();
+    }
+
+    let progress_bar = ProgressBar::new(test_cases.len() as u64);
+    if let Ok(style) = ProgressStyle::with_template("{bar:40} {pos}/{len} test cases") {
+        progress_bar.set_style(style);
+    }
+    // The counter runs across suites, so count from where this one started
+    let completed_before = TEST_CASES_COMPLETED.load(Ordering::Relaxed);
+    let finished = AtomicBool::new(false);
+    let test_run = thread::scope(|scope| {
+        scope.spawn(|| {
+            while !finished.load(Ordering::Relaxed) {
+                progress_bar.set_position((TEST_CASES_COMPLETED.load(Ordering::Relaxed) - completed_before) as u64);
+                thread::sleep(PROGRESS_INTERVAL);
             }
+        });
+        let test_run = run();
+        finished.store(true, Ordering::Relaxed);
+        test_run
+    });
+    progress_bar.finish_and_clear();
+    test_run
+}
+
+/// Write the results in the requested format and the test report, if any
+///
+/// `source` is the robots.txt (or manifest) that was tested, `report_name` names the default
+/// report file and the suites name the test suites inside it.
+fn report_results(args: &Args, test_args: &TestArgs, start: Instant, source: &str, report_name: &str, suites: &[Suite], test_results: &[TestCaseOutput]) -> ExitCode {
+    if test_results.is_empty() && !test_args.allow_empty {
+        eprintln!("error: there are no test cases to run, pass --allow-empty to accept this");
+        return ExitStatus::Usage.into();
+    }
+    if suites.iter().any(|suite| suite.stopped_early) {
+        let failed: usize = suites.iter().map(|suite| suite.summary.failed).sum();
+        eprintln!("Stopped early after {} failures", failed);
+    }
+    if let Some(output_dir) = &test_args.output_dir {
+        if let Err(e) = fs::create_dir_all(output_dir) {
+            eprintln!("error creating output directory {}: {}", output_dir, e);
+            return ExitStatus::Io.into();
+        }
+    }
+    let outputs = test_args.outputs(args);
+    // The text summary says so itself, other formats have nowhere to
+    if test_args.invert && !outputs.iter().any(|(format, _)| matches!(format, OutputFormat::Text | OutputFormat::Github)) {
+        warn!("{}", INVERTED_NOTE);
+    }
+
+    // Every suite is timestamped with the start of the run, and timed by its own run
+    let timestamp = OffsetDateTime::now_utc() - start.elapsed();
+    let junit_suites: Vec<JunitSuite> = suites.iter()
+        .map(|suite| JunitSuite {
+            name: &suite.name,
+            description: suite.description.as_deref(),
+            results: &test_results[suite.results.clone()],
+            timestamp,
+            elapsed: suite.summary.elapsed,
         })
         .collect();
 
-    // Generate JUnit XML
-    let (_, exit_code) = rayon::join(
+    // Read before the report of this run may overwrite it
+    let previous_outcomes = match test_args.diff_against_report.as_deref().map(read_previous_outcomes) {
+        Some(Ok(outcomes)) => Some(outcomes),
+        Some(Err(e)) => return e.report(),
+        None => None,
+    };
+    let delta = previous_outcomes.as_ref().map(|outcomes| diff_against_report(outcomes, test_results));
+
+    // The reported time covers the whole invocation, including reading the inputs
+    let mut summary = TestRunSummary::default();
+    for suite in suites {
+        summary.merge(&suite.summary);
+    }
+    summary.elapsed = start.elapsed();
+
+    // Generate JUnit XML and the summary file
+    let (report_result, (output_result, exit_code)) = rayon::join(
       || {
-          if args.generate_test_report {
-              let test_case_input_file_name = Path::new(&args.test_case_file_path)
-                  .file_name()
-                  .unwrap()
-                  .to_str()
-                  .unwrap()
-                  .replace(".csv", "");
-
-              generate_test_report(&test_results, &test_case_input_file_name);
+          if let Some(report_path) = test_args.report_path(report_name) {
+              generate_test_report(&junit_suites, &report_path, test_args)
+                  .map_err(|e| format!("error writing test report: {}", e))?;
+          }
+          if let Some(summary_path) = test_args.summary_file.as_deref().map(|path| test_args.output_path(path)) {
+              write_summary_file(&summary, &summary_path)
+                  .map_err(|e| format!("error writing summary file {}: {}", summary_path, e))?;
           }
+          Ok::<(), Box<dyn Error + Send + Sync>>(())
       },
       || {
-          let total_test_count = test_results.len();
-          let passed_test_count = test_results.par_iter().filter(|n| n.result).count();
-          let failed_test_count = total_test_count - passed_test_count;
-          println!("Test cases run: {}", total_test_count);
-          println!("Passed tests: {}", passed_test_count);
-          println!("Failed tests: {}", failed_test_count);
-          println!("Elapsed time {:.2}ms", start.elapsed().as_millis());
-          if failed_test_count > 0 { ExitCode::FAILURE } else { ExitCode::SUCCESS }
+          let meets_threshold = match &delta {
+              Some(delta) => delta.newly_failing.is_empty(),
+              None => summary.pass_percentage().is_none_or(|percentage| percentage >= test_args.fail_under),
+          };
+          // Every format is rendered from the same results, stopping at the first that can't be written
+          let output_result = outputs.iter().try_for_each(|(output_format, output_file)| write_output_to(output_file.as_deref(), args.quiet, |output| match output_format {
+              OutputFormat::Text => write_text_results(output, suites, test_results, &summary, args, test_args, delta.as_ref()),
+              OutputFormat::Json => write_json(output, test_results, &summary),
+              OutputFormat::Ndjson => write_ndjson(output, test_results, &summary),
+              OutputFormat::Junit if test_args.only_failures => write_junit_failures(output, &junit_suites),
+              OutputFormat::Junit => write_junit_suites(output, &junit_suites),
+              OutputFormat::Tap => write_tap(output, test_results),
+              OutputFormat::Markdown => write_markdown(output, test_results, &summary),
+              OutputFormat::Html => write_html(output, test_results, &summary, source),
+              OutputFormat::Github => {
+                  for suite in suites {
+                      let file = Some(suite.test_case_file_path.as_str()).filter(|path| *path != STDIN_PATH);
+                      write_github_annotations(output, &test_results[suite.results.clone()], file)?;
+                  }
+                  write_text_results(output, suites, test_results, &summary, args, test_args, delta.as_ref())
+              },
+          }));
+          (output_result, if meets_threshold { ExitCode::SUCCESS } else { ExitStatus::TestFailure.into() })
       }
     );
 
+    if args.quiet {
+        eprintln!(
+            "robots-test total={} passed={} failed={} errored={} elapsed_ms={}",
+            summary.total,
+            summary.passed,
+            summary.failed,
+            summary.errored,
+            summary.elapsed.as_millis()
+        );
+    }
+
+    if let Err(e) = output_result {
+        eprintln!("error writing results: {}", e);
+        return ExitStatus::Io.into();
+    }
+    if let Err(e) = report_result {
+        eprintln!("{}", e);
+        return ExitStatus::Io.into();
+    }
+
     exit_code
 }
 
-fn generate_test_report(test_results: &[TestCaseOutput], test_suite_name: &str) {
+fn run_lint(robots_content: &[u8], args: &Args, conflicts: bool) -> ExitCode {
+    let warnings = lint_warnings(robots_content, conflicts);
+    let output_result = write_output(args, |output| Ok(write_lint_warnings(output, &warnings)?));
+    if let Err(e) = output_result {
+        eprintln!("error writing results: {}", e);
+        return ExitStatus::Io.into();
+    }
+    if args.strict && !warnings.is_empty() { ExitStatus::TestFailure.into() } else { ExitCode::SUCCESS }
+}
+
+fn lint_warnings(robots_content: &[u8], conflicts: bool) -> Vec<LintWarning> {
+    let robots = String::from_utf8_lossy(robots_content);
+    let mut warnings = lint(&robots);
+    if conflicts {
+        warnings.extend(check_conflicts(&robots));
+        warnings.sort_by_key(|warning| warning.line_number);
+    }
+    warnings
+}
+
+fn write_lint_warnings(output: &mut dyn Write, warnings: &[LintWarning]) -> io::Result<()> {
+    for warning in warnings {
+        writeln!(output, "warning: {}", warning)?;
+    }
+    writeln!(output, "Lint warnings: {}", warnings.len())
+}
+
+/// Write the fragments composed into one robots.txt, then lint it and run the test cases against
+/// it, failing if a test case fails or, with --strict, on lint warnings
+fn run_compose(args: &Args, compose_args: &ComposeArgs) -> ExitCode {
+    let mut fragments = Vec::new();
+    for path in &compose_args.fragments {
+        let mut content = match read_robots_file(path) {
+            Ok(content) => content,
+            Err(e) => {
+                eprintln!("error reading robots.txt fragment {}: {}", path, e);
+                return ExitStatus::of_input_error(&e).into();
+            },
+        };
+        // A byte order mark in the middle of the composed file would be taken as part of a line
+        if strip_bom(&mut content) {
+            warn!("robots.txt fragment {} starts with a byte order mark, it was removed", path);
+        }
+        fragments.push((path.as_str(), content));
+    }
+    let fragments: Vec<(&str, &[u8])> = fragments.iter().map(|(path, content)| (*path, content.as_slice())).collect();
+    let mut robots_content = match compose(&fragments) {
+        Ok(robots_content) => robots_content,
+        Err(e) => {
+            eprintln!("error composing robots.txt: {}", e);
+            return ExitStatus::Parse.into();
+        },
+    };
+    if let Err(e) = fs::write(&compose_args.composed_path, &robots_content) {
+        eprintln!("error writing composed robots.txt {}: {}", compose_args.composed_path, e);
+        return ExitStatus::Io.into();
+    }
+
+    let robots_source = compose_args.composed_path.as_str();
+    if let Err(e) = check_robots_content(robots_source, &mut robots_content, args) {
+        return e.report();
+    }
+    if let Some(path) = &args.print_effective_robots {
+        if let Err(e) = print_effective_robots(robots_source, &robots_content, path.as_deref()) {
+            eprintln!("error writing the effective robots.txt: {}", e);
+            return ExitStatus::Io.into();
+        }
+    }
+
+    let warnings = lint_warnings(&robots_content, compose_args.check_conflicts);
+    let test_run = match &compose_args.test_case_file_path {
+        Some(test_case_file_path) => {
+            let test_cases = match get_test_cases(test_case_file_path, args, &compose_args.loading) {
+                Ok(test_case_file) => test_case_file.test_cases,
+                Err(e) => {
+                    eprintln!("error getting test cases from {}: {}", test_case_file_path, e);
+                    return ExitStatus::of_input_error(e.as_ref()).into();
+                },
+            };
+            Some(run_tests(&robots_content, &test_cases))
+        },
+        None => None,
+    };
+
+    let output_result = write_output(args, |output| {
+        write_lint_warnings(output, &warnings)?;
+        if let Some(test_run) = &test_run {
+            write_summary(output, &[], &test_run.results, &test_run.summary, args.verbose, false, false)?;
+        }
+        Ok(())
+    });
+    if let Err(e) = output_result {
+        eprintln!("error writing results: {}", e);
+        return ExitStatus::Io.into();
+    }
+
+    let tests_passed = test_run.as_ref().is_none_or(|test_run| test_run.summary.passed == test_run.summary.total);
+    if !tests_passed || (args.strict && !warnings.is_empty()) { ExitStatus::TestFailure.into() } else { ExitCode::SUCCESS }
+}
+
+fn list_sitemaps(robots_content: &[u8], robots_source: &str, args: &Args) -> ExitCode {
+    let r = match Robot::new("*", robots_content) {
+        Ok(r) => r,
+        Err(e) => {
+            eprintln!("error parsing robots.txt {}: {:#}", robots_source, e);
+            return ExitStatus::Parse.into();
+        },
+    };
+
+    let output_result = write_output(args, |output| {
+        for sitemap in &r.sitemaps {
+            writeln!(output, "{}", sitemap)?;
+        }
+        Ok(())
+    });
+    if let Err(e) = output_result {
+        eprintln!("error writing results: {}", e);
+        return ExitStatus::Io.into();
+    }
+    ExitCode::SUCCESS
+}
+
+/// Print whether each URL of the list is allowed for the user agent
+fn run_classify(robots_content: &[u8], robots_source: &str, args: &Args, url_list: &str, user_agent: &str, classify_format: ClassifyFormat) -> ExitCode {
+    let r = match Robot::new(user_agent, &normalize_rule_escapes(robots_content)) {
+        Ok(r) => r,
+        Err(e) => {
+            eprintln!("error parsing robots.txt {}: {:#}", robots_source, e);
+            return ExitStatus::Parse.into();
+        },
+    };
+    let mut url_list_content = String::new();
+    if let Err(e) = open_input(url_list).and_then(|mut input| input.read_to_string(&mut url_list_content)) {
+        eprintln!("error reading URL list {}: {}", url_list, e);
+        return ExitStatus::of_input_error(&e).into();
+    }
+
+    let decisions: Vec<(&str, bool)> = url_list_content.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(|url| (url, r.allowed(&normalize_url(url))))
+        .collect();
+    let output_result = write_output(args, |output| match classify_format {
+        ClassifyFormat::Text => {
+            for (url, allowed) in &decisions {
+                writeln!(output, "{} {}", url, decision_label(*allowed))?;
+            }
+            let allowed = decisions.iter().filter(|(_, allowed)| *allowed).count();
+            writeln!(output, "Allowed: {}, denied: {}", allowed, decisions.len() - allowed)?;
+            Ok(())
+        },
+        ClassifyFormat::Json => write_json_url_decisions(output, user_agent, &decisions),
+        ClassifyFormat::Csv => write_csv_url_decisions(output, user_agent, &decisions),
+    });
+    if let Err(e) = output_result {
+        eprintln!("error writing results: {}", e);
+        return ExitStatus::Io.into();
+    }
+    ExitCode::SUCCESS
+}
+
+/// Match the URL, or the test cases' URLs, over and over and print how fast that was
+fn run_bench(robots_content: &[u8], robots_source: &str, args: &Args, bench_args: &BenchArgs) -> ExitCode {
+    let test_cases = match &bench_args.test_case_file_path {
+        Some(test_case_file_path) => match get_test_cases(test_case_file_path, args, &bench_args.loading) {
+            Ok(test_case_file) => test_case_file.test_cases,
+            Err(e) => {
+                eprintln!("error getting test cases from {}: {}", test_case_file_path, e);
+                return ExitStatus::of_input_error(e.as_ref()).into();
+            },
+        },
+        None => Vec::new(),
+    };
+    let targets: Vec<(&str, &str)> = match &bench_args.url {
+        Some(url) => vec![(bench_args.user_agent.as_str(), url.as_str())],
+        // Sitemap and host assertions don't go through the matcher
+        None => test_cases.iter()
+            .filter(|test| !test.is_sitemap_assertion() && !test.is_host_assertion())
+            .map(|test| (test.user_agent.as_str(), test.url.as_str()))
+            .collect(),
+    };
+    if targets.is_empty() {
+        eprintln!("error: test case file {} has no URLs to match", bench_args.test_case_file_path.as_deref().unwrap_or_default());
+        return ExitStatus::Usage.into();
+    }
+
+    let warmup = Duration::from_secs(bench_args.warmup_seconds);
+    let duration = Duration::from_secs(bench_args.seconds);
+    let result = match bench(robots_content, &targets, warmup, duration) {
+        Ok(result) => result,
+        Err(e) => {
+            eprintln!("error parsing robots.txt {}: {}", robots_source, e);
+            return ExitStatus::Parse.into();
+        },
+    };
+    let output_result = write_output(args, |output| {
+        writeln!(output, "Matches: {}", result.matches)?;
+        writeln!(output, "Matches per second: {:.0}", result.matches_per_second())?;
+        writeln!(output, "p50 latency: {:?}", result.p50)?;
+        writeln!(output, "p99 latency: {:?}", result.p99)?;
+        if let Some(index) = result.slowest {
+            let (user_agent, url) = targets[index];
+            writeln!(output, "Slowest on average: {} {}", user_agent, url)?;
+        }
+        Ok(())
+    });
+    if let Err(e) = output_result {
+        eprintln!("error writing results: {}", e);
+        return ExitStatus::Io.into();
+    }
+    ExitCode::SUCCESS
+}
+
+/// Print the rule set hash or canonical rules, failing if the hash isn't the expected one
+fn run_hash(robots_content: &[u8], args: &Args, expected_hash: Option<&str>, canonical: bool) -> ExitCode {
+    let robots = String::from_utf8_lossy(robots_content);
+    let hash = rule_set_hash(&robots);
+
+    let output_result = write_output(args, |output| {
+        if canonical {
+            write!(output, "{}", canonical_rules(&robots))?;
+        } else {
+            writeln!(output, "{}", hash)?;
+        }
+        Ok(())
+    });
+    if let Err(e) = output_result {
+        eprintln!("error writing results: {}", e);
+        return ExitStatus::Io.into();
+    }
+    match expected_hash {
+        Some(expected_hash) if !expected_hash.trim().eq_ignore_ascii_case(&hash) => {
+            eprintln!("error: rule set hash {} differs from the expected {}", hash, expected_hash.trim());
+            ExitStatus::TestFailure.into()
+        },
+        _ => ExitCode::SUCCESS,
+    }
+}
+
+/// Print the totals of each suite when a run covers more than one robots.txt
+fn write_suite_lines(output: &mut dyn Write, suites: &[Suite]) -> io::Result<()> {
+    if suites.len() < 2 {
+        return Ok(());
+    }
+
+    for suite in suites {
+        writeln!(
+            output,
+            "{}: {} run, {} passed, {} failed",
+            suite.name,
+            suite.summary.total,
+            suite.summary.passed,
+            suite.summary.failed
+        )?;
+    }
+    Ok(())
+}
+
+/// Print the description of each suite that has one, prefixed with the suite name when there are several
+fn write_description_lines(output: &mut dyn Write, suites: &[Suite]) -> io::Result<()> {
+    for suite in suites {
+        let Some(description) = &suite.description else { continue };
+        if suites.len() < 2 {
+            writeln!(output, "Description: {}", description)?;
+        } else {
+            writeln!(output, "{}: Description: {}", suite.name, description)?;
+        }
+    }
+    Ok(())
+}
 
-    let mut test_cases: Vec<TestCase> = Vec::new();
+/// Print the host each suite's robots.txt declares, prefixed with the suite name when there are several
+fn write_host_lines(output: &mut dyn Write, suites: &[Suite]) -> io::Result<()> {
+    for suite in suites {
+        let host = declared_host(&suite.robots).map_or("none", |line| line.value);
+        if suites.len() < 2 {
+            writeln!(output, "Host: {}", host)?;
+        } else {
+            writeln!(output, "{}: Host: {}", suite.name, host)?;
+        }
+    }
+    Ok(())
+}
 
+/// Print how many of each user agent's test cases passed, such as `googlebot: 45/50 passed`
+fn write_agent_lines(output: &mut dyn Write, test_results: &[TestCaseOutput]) -> io::Result<()> {
+    let mut by_agent: BTreeMap<&str, (usize, usize)> = BTreeMap::new();
     for result in test_results {
-        let test_case_name = get_test_case_name(result);
+        let (passed, total) = by_agent.entry(&result.user_agent).or_default();
+        *passed += usize::from(result.passed());
+        *total += 1;
+    }
+    for (user_agent, (passed, total)) in by_agent {
+        writeln!(output, "{}: {}/{} passed", user_agent, passed, total)?;
+    }
+    Ok(())
+}
 
-        match result.result {
-            true => {
-                let test_success = TestCaseBuilder::success(&test_case_name, Duration::seconds(0))
-                .build();
-                test_cases.push(test_success);
+/// Print each rule's test case matches, per suite when there are several
+fn write_rule_coverage(output: &mut dyn Write, suites: &[Suite], test_results: &[TestCaseOutput]) -> io::Result<()> {
+    let mut untested = 0;
+    for suite in suites {
+        if suites.len() < 2 {
+            writeln!(output, "Rule coverage:")?;
+        } else {
+            writeln!(output, "Rule coverage for {}:", suite.name)?;
+        }
+        for coverage in rule_coverage(&suite.robots, &test_results[suite.results.clone()]) {
+            let rule = coverage.rule;
+            let flag = if coverage.matches == 0 { " (untested)" } else { "" };
+            writeln!(output, "  line {}: {}: {} matched {}{}", rule.line_number, rule.key, rule.value, coverage.matches, flag)?;
+            if coverage.matches == 0 {
+                untested += 1;
             }
-            false => {
-                    let test_failure: TestCase = TestCase::failure(
-                        &test_case_name,
-                        Duration::seconds(0),
-                        "assert_eq",
-                        "not equal",
-                    );
-                    test_cases.push(test_failure);
-                }
         }
     }
+    writeln!(output, "Untested rules: {}", untested)
+}
 
-    let test_suite = TestSuiteBuilder::new(test_suite_name)
-        .add_testcases(test_cases)
-        .build();
+/// Print the human readable results of the text format, which the github format follows its
+/// annotations with: the descriptions, suite totals and summary, then what the options add
+fn write_text_results(
+    output: &mut dyn Write,
+    suites: &[Suite],
+    test_results: &[TestCaseOutput],
+    summary: &TestRunSummary,
+    args: &Args,
+    test_args: &TestArgs,
+    delta: Option<&ReportDelta>,
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+    write_description_lines(output, suites)?;
+    if test_args.show_host {
+        write_host_lines(output, suites)?;
+    }
+    write_suite_lines(output, suites)?;
+    write_summary(output, suites, test_results, summary, args.verbose, test_args.color.enabled(args), test_args.show_rule)?;
+    if test_args.by_agent {
+        write_agent_lines(output, test_results)?;
+    }
+    if test_args.invert {
+        writeln!(output, "Inverted: {}", INVERTED_NOTE)?;
+    }
+    if test_args.pattern_coverage {
+        write_rule_coverage(output, suites, test_results)?;
+    }
+    if let Some(delta) = delta {
+        write_report_delta(output, delta)?;
+    }
+    Ok(())
+}
 
-    let r = ReportBuilder::new()
-        .add_testsuite(test_suite)
-        .build();
+/// Print the human readable summary, preceded by the test case lines `verbose` asks for
+fn write_summary(output: &mut dyn Write, suites: &[Suite], test_results: &[TestCaseOutput], summary: &TestRunSummary, verbose: u8, color: bool, show_rule: bool) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let checks_crawl_delay = test_results.iter().any(|n| n.expected_crawl_delay.is_some());
 
-    let mut file = File::create(format!("./{}.robots-test-results.xml", &test_suite_name)).unwrap();
-    r.write_xml(&mut file).unwrap();
-    file.flush().unwrap();
-    file.sync_all().unwrap();
+    write_test_case_lines(output, suites, test_results, verbose, color, show_rule)?;
+    writeln!(output, "Test cases run: {}", summary.total)?;
+    writeln!(output, "Passed tests: {}", paint(summary.passed, PASSED_STYLE, color))?;
+    if summary.failed > 0 {
+        writeln!(output, "Failed tests: {}", paint(summary.failed, FAILED_STYLE, color))?;
+    } else {
+        writeln!(output, "Failed tests: {}", summary.failed)?;
+    }
+    if summary.errored > 0 {
+        writeln!(output, "Errored tests: {}", paint(summary.errored, FAILED_STYLE, color))?;
+    }
+    if checks_crawl_delay {
+        writeln!(output, "Allow/deny mismatches: {}", summary.allow_mismatches)?;
+        writeln!(output, "Crawl-delay mismatches: {}", summary.crawl_delay_mismatches)?;
+    }
+    writeln!(output, "Elapsed time {:.2}ms", summary.elapsed.as_millis())?;
+    if let Some(percentage) = summary.pass_percentage() {
+        writeln!(output, "Passed {}/{} ({:.1}%)", summary.passed, summary.total, percentage)?;
+    }
+    Ok(())
 }
 
-fn get_test_case_name(result: &TestCaseOutput) -> String {
-    let expected_result_label = if result.expected_result { "allowed" } else { "denied" };
-    format!("Accessing URL: {} as {} should be {}", result.url, result.user_agent, expected_result_label)
+/// Print the test cases whose outcome changed since the earlier report
+fn write_report_delta(output: &mut dyn Write, delta: &ReportDelta) -> io::Result<()> {
+    for result in &delta.newly_failing {
+        writeln!(output, "Newly failing: {}", get_test_case_name(result))?;
+    }
+    for result in &delta.newly_passing {
+        writeln!(output, "Newly passing: {}", get_test_case_name(result))?;
+    }
+    writeln!(output, "New failures: {}, newly passing: {}", delta.newly_failing.len(), delta.newly_passing.len())?;
+    Ok(())
 }
 
-struct TestCaseDefinition {
-    user_agent: String,
-    url: String,
-    expected_result: bool
+/// Print `allowed` or `denied`, succeeding either way as nothing is expected
+fn run_check(robots_content: &[u8], robots_source: &str, args: &Args, url: &str, user_agent: &str) -> ExitCode {
+    let r = match Robot::new(user_agent, &normalize_rule_escapes(robots_content)) {
+        Ok(r) => r,
+        Err(e) => {
+            eprintln!("error parsing robots.txt {}: {:#}", robots_source, e);
+            return ExitStatus::Parse.into();
+        },
+    };
+
+    let output_result = write_output(args, |output| {
+        writeln!(output, "{}", decision_label(r.allowed(&normalize_url(url))))?;
+        Ok(())
+    });
+    if let Err(e) = output_result {
+        eprintln!("error writing results: {}", e);
+        return ExitStatus::Io.into();
+    }
+    ExitCode::SUCCESS
+}
+
+fn run_explain(robots_content: &[u8], robots_source: &str, args: &Args, url: &str, user_agent: &str, explain_format: ExplainFormat) -> ExitCode {
+    let robots = String::from_utf8_lossy(robots_content);
+    let explanation = match explain(&robots, user_agent, url) {
+        Ok(explanation) => explanation,
+        Err(e) => {
+            eprintln!("error parsing robots.txt {}: {}", robots_source, e);
+            return ExitStatus::Parse.into();
+        },
+    };
+
+    let output_result = write_output(args, |output| {
+        if explain_format == ExplainFormat::Json {
+            return write_json_explanation(output, url, user_agent, &explanation);
+        }
+        writeln!(output, "Accessing URL: {} as {} is {}", url, user_agent, decision_label(explanation.allowed))?;
+        match explanation.matching_rule {
+            Some(rule) => writeln!(output, "Matching rule: line {}: {}: {}", rule.line_number, rule.key, rule.value)?,
+            None => writeln!(output, "Matching rule: none")?,
+        }
+        writeln!(output, "Crawl-delay: {}", crawl_delay_label(explanation.crawl_delay))?;
+        writeln!(output, "Rules for User-agent: {}", explanation.group_user_agent)?;
+        for rule in &explanation.rules {
+            writeln!(output, "  line {}: {}: {}", rule.line_number, rule.key, rule.value)?;
+        }
+        Ok(())
+    });
+    if let Err(e) = output_result {
+        eprintln!("error writing results: {}", e);
+        return ExitStatus::Io.into();
+    }
+    ExitCode::SUCCESS
+}
+
+fn run_explain_pattern(robots_content: &[u8], robots_source: &str, args: &Args, path: &str, user_agent: &str) -> ExitCode {
+    let robots = String::from_utf8_lossy(robots_content);
+    let explanation = match explain(&robots, user_agent, path) {
+        Ok(explanation) => explanation,
+        Err(e) => {
+            eprintln!("error parsing robots.txt {}: {}", robots_source, e);
+            return ExitStatus::Parse.into();
+        },
+    };
+    let traces = trace_patterns(&explanation, path);
+
+    let output_result = write_output(args, |output| {
+        writeln!(output, "Rules for User-agent: {}", explanation.group_user_agent)?;
+        for trace in &traces {
+            let rule = format!("line {}: {}: {}", trace.rule.line_number, trace.rule.key, trace.rule.value);
+            if !trace.matches {
+                writeln!(output, "  {} does not match", rule)?;
+                continue;
+            }
+            let mut tokens: Vec<String> = trace.wildcards.iter()
+                .map(|wildcard| format!("* = {:?}", wildcard))
+                .collect();
+            if trace.anchored {
+                tokens.push("$ = end of URL".to_string());
+            }
+            if tokens.is_empty() {
+                writeln!(output, "  {} matches", rule)?;
+            } else {
+                writeln!(output, "  {} matches, {}", rule, tokens.join(", "))?;
+            }
+        }
+        match explanation.matching_rule {
+            Some(rule) => writeln!(output, "Deciding rule: line {}: {}: {}", rule.line_number, rule.key, rule.value)?,
+            None => writeln!(output, "Deciding rule: none")?,
+        }
+        writeln!(output, "Accessing URL: {} as {} is {}", path, user_agent, decision_label(explanation.allowed))?;
+        Ok(())
+    });
+    if let Err(e) = output_result {
+        eprintln!("error writing results: {}", e);
+        return ExitStatus::Io.into();
+    }
+    ExitCode::SUCCESS
+}
+
+/// Print one line per test case, failures first and then sorted by user agent and url so runs
+/// diff cleanly
+///
+/// With `show_rule` each allow/deny line ends with the rule that decided it, and failures are
+/// printed even without `verbose`.
+fn write_test_case_lines(output: &mut dyn Write, suites: &[Suite], test_results: &[TestCaseOutput], verbose: u8, color: bool, show_rule: bool) -> io::Result<()> {
+    let status = |passed: bool| if passed { paint("PASS", PASSED_STYLE, color) } else { paint("FAIL", FAILED_STYLE, color) };
+    if verbose == 0 && !show_rule {
+        return Ok(());
+    }
+
+    let mut shown: Vec<usize> = (0..test_results.len())
+        .filter(|&index| !test_results[index].passed() || verbose > 1)
+        .collect();
+    shown.sort_by_key(|&index| {
+        let result = &test_results[index];
+        (result.passed(), report_order(result))
+    });
+
+    for index in shown {
+        let result = &test_results[index];
+        let name = result.name.as_ref().map(|name| format!("{}: ", name)).unwrap_or_default();
+        if let Some(error) = &result.error {
+            writeln!(output, "{}: {}{} {} {}", paint("ERROR", FAILED_STYLE, color), name, result.user_agent, result.url, error)?;
+            continue;
+        }
+        if result.checks_url() {
+            let rule = match suites.iter().find(|suite| suite.results.contains(&index)) {
+                Some(suite) if show_rule && !result.is_sitemap_assertion() && !result.is_host_assertion() => format!(" rule={}", matching_rule_label(&suite.robots, result)),
+                _ => String::new(),
+            };
+            writeln!(
+                output,
+                "{}: {}{} {} expected={} got={}{}{}",
+                status(result.result),
+                name,
+                result.user_agent,
+                result.url,
+                result.result_label(result.expected_result),
+                result.result_label(result.actual_result()),
+                rule,
+                reason_label(result, result.result)
+            )?;
+        }
+        if result.expected_crawl_delay.is_some() {
+            writeln!(
+                output,
+                "{}: {}{} crawl-delay expected={} got={}{}",
+                status(result.crawl_delay_result()),
+                name,
+                result.user_agent,
+                crawl_delay_label(result.expected_crawl_delay),
+                crawl_delay_label(result.crawl_delay),
+                reason_label(result, result.crawl_delay_result())
+            )?;
+        }
+    }
+    Ok(())
+}
+
+/// The test case's reason for its expectations, quoted, on the line of a failed assertion
+fn reason_label(result: &TestCaseOutput, passed: bool) -> String {
+    match &result.reason {
+        Some(reason) if !passed => format!(" reason=\"{}\"", reason),
+        _ => String::new(),
+    }
+}
+
+/// The longest `Allow` or `Disallow` rule matching the test case's url, quoted, or `none`
+fn matching_rule_label(robots: &str, result: &TestCaseOutput) -> String {
+    match explain(robots, &result.user_agent, &result.url).ok().and_then(|explanation| explanation.matching_rule) {
+        Some(rule) => format!("\"line {}: {}: {}\"", rule.line_number, rule.key, rule.value),
+        None => "none".to_string(),
+    }
+}
+
+/// Wrap text in the style's escape codes when colour is enabled
+fn paint(text: impl Display, style: Style, color: bool) -> String {
+    if color {
+        format!("{}{}{}", style.render(), text, style.render_reset())
+    } else {
+        text.to_string()
+    }
 }
 
-struct TestCaseOutput<'a> {
-    user_agent: &'a str,
-    url: &'a str,
-    expected_result: bool,
-    result: bool
+fn write_output(
+    args: &Args,
+    write: impl FnOnce(&mut dyn Write) -> Result<(), Box<dyn Error + Send + Sync>>,
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+    write_output_to(args.output_file.as_deref(), args.quiet, write)
+}
+
+/// Write to the given file, or to stdout unless `quiet`
+fn write_output_to(
+    output_file: Option<&str>,
+    quiet: bool,
+    write: impl FnOnce(&mut dyn Write) -> Result<(), Box<dyn Error + Send + Sync>>,
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let mut output: Box<dyn Write> = match output_file {
+        Some(path) => Box::new(File::create(path)?),
+        None if quiet => Box::new(io::sink()),
+        None => Box::new(io::stdout()),
+    };
+    write(&mut output)?;
+    output.flush()?;
+    Ok(())
+}
+
+fn generate_test_report(suites: &[JunitSuite], report_path: &str, test_args: &TestArgs) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let mut report = Vec::new();
+    if test_args.only_failures {
+        write_junit_failures(&mut report, suites)?;
+    } else {
+        write_junit_suites(&mut report, suites)?;
+    }
+    // A report that doesn't exist yet is created, as without --append
+    let existing = match test_args.append.then(|| fs::read_to_string(report_path)) {
+        Some(Ok(existing)) => Some(existing),
+        Some(Err(e)) if e.kind() != io::ErrorKind::NotFound => return Err(e.into()),
+        _ => None,
+    };
+
+    let mut file = File::create(report_path)?;
+    match existing {
+        Some(existing) => merge_junit_reports(&mut file, &existing, &String::from_utf8(report)?)?,
+        None => file.write_all(&report)?,
+    }
+    file.flush()?;
+    if test_args.durable_report {
+        file.sync_all()?;
+    }
+    Ok(())
 }
 
-fn get_test_cases(file_path: &str) -> Result<Vec<TestCaseDefinition>, Box<dyn Error>> {
-    let test_case_content = fs::read_to_string(file_path)?;
-    let mut test_cases: Vec<TestCaseDefinition> = Vec::with_capacity(test_case_content.lines().count());
-    let mut rdr = csv::Reader::from_reader(test_case_content.as_bytes());
+fn read_previous_outcomes(report_path: &str) -> Result<HashMap<String, bool>, RunError> {
+    let report = fs::read_to_string(report_path).map_err(|e| {
+        RunError::new(ExitStatus::of_input_error(&e), format!("error reading report {}: {}", report_path, e))
+    })?;
+    read_junit_outcomes(&report)
+        .map_err(|e| RunError::new(ExitStatus::Parse, format!("error reading report {}: {}", report_path, e)))
+}
 
-    for result in rdr.records() {
-        let record = result?;
+fn write_summary_file(summary: &TestRunSummary, summary_path: &str) -> Result<(), Box<dyn Error + Send + Sync>> {
+    if let Some(directory) = Path::new(summary_path).parent().filter(|directory| !directory.as_os_str().is_empty()) {
+        fs::create_dir_all(directory)?;
+    }
+    let mut file = File::create(summary_path)?;
+    write_json_summary(&mut file, summary)?;
+    file.flush()?;
+    Ok(())
+}
 
-        let test_case = TestCaseDefinition {
-            user_agent: record[0].to_string(),
-            url: record[1].to_string(),
-            expected_result: record[2].parse::<LenientBool>().unwrap().into(),
+fn get_test_cases(file_path: &str, args: &Args, loading: &TestCaseLoadingArgs) -> Result<TestCaseFile, Box<dyn Error>> {
+    let mut input = open_input(file_path)?;
+    let format = match loading.test_format.or_else(|| TestCaseSource::from_path(Path::new(file_path))) {
+        Some(format) => format,
+        // stdin has no extension to go by, so it is read up front to look at how it starts
+        None if file_path == STDIN_PATH => {
+            let mut content = Vec::new();
+            input.read_to_end(&mut content)?;
+            let format = TestCaseSource::sniff(&content)?;
+            input = Box::new(io::Cursor::new(content));
+            format
+        },
+        None => TestCaseSource::Csv,
+    };
+    let mut csv_options = CsvOptions::from_path(Path::new(file_path));
+    csv_options.has_headers = !loading.no_header;
+    if let Some(delimiter) = loading.delimiter {
+        csv_options.delimiter = delimiter;
+    }
+    csv_options.default_user_agent = loading.default_user_agent.clone();
+    let mut test_case_file = load_test_case_file(input, format, &csv_options)?;
+    for (index, test) in test_case_file.test_cases.iter().enumerate() {
+        let url_length = test.url.chars().count();
+        if url_length <= loading.max_url_length {
+            continue;
+        }
+        let position = match test.line_number {
+            Some(line_number) => format!("line {}", line_number),
+            None => format!("test case {}", index + 1),
         };
+        let problem = format!("{}: url is {} characters, longer than --max-url-length {}", position, url_length, loading.max_url_length);
+        if args.strict {
+            return Err(problem.into());
+        }
+        warn!("{} {}", file_path, problem);
+    }
+    if !loading.dedupe {
+        return Ok(test_case_file);
+    }
+
+    let (test_cases, removed) = dedupe_test_cases(test_case_file.test_cases)?;
+    if removed > 0 && !args.quiet {
+        eprintln!("Removed {} duplicate test cases from {}", removed, file_path);
+    }
+    test_case_file.test_cases = test_cases;
+    Ok(test_case_file)
+}
+
+fn parse_percentage(value: &str) -> Result<f64, String> {
+    match value.trim_end_matches('%').parse::<f64>() {
+        Ok(percentage) if (0.0..=100.0).contains(&percentage) => Ok(percentage),
+        _ => Err(format!("{:?} is not a percentage between 0 and 100", value)),
+    }
+}
+
+/// Parse a `FORMAT` or `FORMAT:PATH` output format
+fn parse_output_spec(value: &str) -> Result<OutputSpec, String> {
+    let (format, path) = match value.split_once(':') {
+        Some((format, path)) => (format, Some(path.to_string())),
+        None => (value, None),
+    };
+    let format = OutputFormat::from_str(format, false).map_err(|_| {
+        let formats: Vec<String> = OutputFormat::value_variants().iter()
+            .filter_map(|format| format.to_possible_value())
+            .map(|format| format.get_name().to_string())
+            .collect();
+        format!("{:?} is not one of the formats {}", format, formats.join(", "))
+    })?;
+    if path.as_deref() == Some("") {
+        return Err(format!("{:?} has no path after the :", value));
+    }
+    Ok(OutputSpec { format, path })
+}
+
+/// Check a site is an http or https origin, returning it without a trailing `/`
+fn parse_site(value: &str) -> Result<String, String> {
+    if !value.contains("://") {
+        return Err(format!("{:?} has no scheme, try https://{}", value, value));
+    }
+    let url = Url::parse(value).map_err(|e| format!("{:?} is not a URL: {}", value, e))?;
+    if !matches!(url.scheme(), "http" | "https") {
+        return Err(format!("{:?} is not an http or https URL", value));
+    }
+    if url.host_str().is_none() || url.path() != "/" || url.query().is_some() || url.fragment().is_some() || !url.username().is_empty() {
+        return Err(format!("{:?} is not an origin such as https://example.com, without a path", value));
+    }
+    Ok(url.origin().ascii_serialization())
+}
+
+fn parse_delimiter(value: &str) -> Result<u8, String> {
+    match value {
+        "\\t" | "tab" => Ok(b'\t'),
+        _ if value.len() == 1 && value.is_ascii() => Ok(value.as_bytes()[0]),
+        _ => Err(format!("{:?} is not a single ASCII character", value)),
+    }
+}
+
+/// Name of a file without its extension, or `stdin` for `-`
+fn get_file_stem(path: &str) -> String {
+    if path == STDIN_PATH {
+        "stdin".to_string()
+    } else {
+        Path::new(path)
+            .file_stem()
+            .map_or_else(String::new, |stem| stem.to_string_lossy().into_owned())
+    }
+}
+
+/// Open a file for reading, treating `-` as stdin
+fn open_input(path: &str) -> io::Result<Box<dyn Read>> {
+    if path == STDIN_PATH {
+        Ok(Box::new(io::stdin()))
+    } else {
+        Ok(Box::new(File::open(path)?))
+    }
+}
+
+/// Fetch the robots.txt at `url`, reusing the copy in --cache-dir while it hasn't expired
+///
+/// A cache that can't be read or written is warned about and otherwise ignored.
+fn fetch_robots_cached(url: &str, args: &Args, retry: &RetryPolicy, log_retry: impl FnMut(&str)) -> Result<Vec<u8>, Box<dyn Error>> {
+    let Some(cache_dir) = &args.cache_dir else {
+        return fetch_robots(url, &args.user_agent_header, retry, log_retry);
+    };
+    let cache = FetchCache::new(cache_dir, Duration::from_secs(args.cache_ttl));
+    let cache_path = cache.path(url);
+
+    match cache.get(url) {
+        Ok(Some(content)) => {
+            info!("Using the cached copy of {} from {}", url, cache_path.display());
+            return Ok(content);
+        },
+        Ok(None) => {},
+        Err(e) => warn!("unable to read cached robots.txt {}: {}", cache_path.display(), e),
+    }
+
+    let fetched = fetch_robots_with_max_age(url, &args.user_agent_header, retry, log_retry)?;
+    info!("Fetched {}", url);
+    if let Err(e) = cache.put(url, &fetched.body, fetched.max_age) {
+        warn!("unable to cache robots.txt in {}: {}", cache_path.display(), e);
+    }
+    Ok(fetched.body)
+}
 
-        test_cases.push(test_case);
+/// Write the bytes the matcher parses for the robots.txt to `path`, or stderr after a line
+/// saying what they are
+fn print_effective_robots(robots_source: &str, robots_content: &[u8], path: Option<&str>) -> io::Result<()> {
+    let effective = normalize_rule_escapes(robots_content);
+    match path {
+        Some(path) => fs::write(path, &effective),
+        None => {
+            let mut stderr = io::stderr().lock();
+            writeln!(stderr, "Effective robots.txt {}, {} bytes:", robots_source, effective.len())?;
+            stderr.write_all(&effective)?;
+            if !effective.ends_with(b"\n") {
+                writeln!(stderr)?;
+            }
+            stderr.flush()
+        },
     }
-    Ok(test_cases)
+}
+
+/// Read robots.txt content from a file or stdin, decompressing it if it is gzipped
+fn read_robots_file(path: &str) -> io::Result<Vec<u8>> {
+    let mut content = Vec::new();
+    open_input(path)?.read_to_end(&mut content)?;
+    decompress_robots(content, Some(Path::new(path)).filter(|_| path != STDIN_PATH))
 }
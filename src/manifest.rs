@@ -0,0 +1,104 @@
+//! Manifests pairing robots.txt files with their test cases, for testing several sites in one run
+use std::error::Error;
+use std::fs::File;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+use serde::Deserialize;
+
+use crate::TestCaseSource;
+
+/// A robots.txt file and the test cases it should be checked against
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ManifestEntry {
+    /// robots.txt path, resolved against the manifest's directory
+    pub robots_path: PathBuf,
+    /// test case file path, resolved against the manifest's directory
+    pub test_case_path: PathBuf,
+}
+
+/// Load a manifest of `robots_txt,test_cases` CSV rows, or a JSON or YAML list of
+/// `{robots_txt, test_cases}` entries, detected from the file extension
+///
+/// A CSV manifest may start with a header row naming the two columns, or leave it out to give
+/// them in that order. Relative paths are resolved against the directory containing the manifest.
+pub fn load_manifest(path: &Path) -> Result<Vec<ManifestEntry>, Box<dyn Error>> {
+    let file = File::open(path)?;
+    let entries: Vec<StructuredManifestEntry> = match TestCaseSource::from_path(path) {
+        Some(TestCaseSource::Json) => serde_json::from_reader(file)?,
+        Some(TestCaseSource::Yaml) => serde_yaml::from_reader(file)?,
+        Some(TestCaseSource::Csv) | None => load_csv_entries(file)?,
+    };
+
+    let base = path.parent().unwrap_or_else(|| Path::new(""));
+    Ok(entries.into_iter()
+        .map(|entry| ManifestEntry {
+            robots_path: base.join(entry.robots_txt),
+            test_case_path: base.join(entry.test_cases),
+        })
+        .collect())
+}
+
+#[derive(Deserialize)]
+struct StructuredManifestEntry {
+    robots_txt: String,
+    test_cases: String,
+}
+
+/// Read `robots_txt,test_cases` rows, the columns looked up by name when the first row is a
+/// header naming them, in any order. A first row naming neither is the first entry
+fn load_csv_entries(reader: impl Read) -> Result<Vec<StructuredManifestEntry>, Box<dyn Error>> {
+    let mut entries = Vec::new();
+    let mut rdr = csv::Reader::from_reader(reader);
+
+    let headers = rdr.headers()?.clone();
+    let find = |name: &str| headers.iter().position(|header| header.trim().eq_ignore_ascii_case(name));
+    let (columns, first_row) = match (find("robots_txt"), find("test_cases")) {
+        (Some(robots_txt), Some(test_cases)) => ((robots_txt, test_cases), None),
+        (None, None) => ((0, 1), Some(headers.clone())),
+        (Some(_), None) => return Err("header: missing test_cases column".into()),
+        (None, Some(_)) => return Err("header: missing robots_txt column".into()),
+    };
+
+    for result in first_row.map(Ok).into_iter().chain(rdr.records()) {
+        let record = result?;
+        let line = record.position().map_or(0, |position| position.line());
+        match (record.get(columns.0), record.get(columns.1)) {
+            (Some(robots_txt), Some(test_cases)) if !robots_txt.is_empty() && !test_cases.is_empty() => {
+                entries.push(StructuredManifestEntry {
+                    robots_txt: robots_txt.to_string(),
+                    test_cases: test_cases.to_string(),
+                });
+            },
+            _ => return Err(format!("line {}: expected a robots_txt and a test_cases path", line).into()),
+        }
+    }
+    Ok(entries)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn paths(csv: &str) -> Vec<(String, String)> {
+        load_csv_entries(csv.as_bytes()).unwrap().into_iter().map(|entry| (entry.robots_txt, entry.test_cases)).collect()
+    }
+
+    #[test]
+    fn header_columns_may_come_in_any_order() {
+        assert_eq!(paths("test_cases,robots_txt\na.csv,a.txt\n"), [("a.txt".to_string(), "a.csv".to_string())]);
+    }
+
+    #[test]
+    fn first_row_without_a_header_is_an_entry() {
+        assert_eq!(paths("a.txt,a.csv\nb.txt,b.csv\n"), [
+            ("a.txt".to_string(), "a.csv".to_string()),
+            ("b.txt".to_string(), "b.csv".to_string()),
+        ]);
+    }
+
+    #[test]
+    fn header_needs_both_columns() {
+        assert!(load_csv_entries("robots_txt,tests\na.txt,a.csv\n".as_bytes()).is_err());
+    }
+}
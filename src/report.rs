@@ -0,0 +1,764 @@
+//! Rendering test results in machine readable formats
+use std::collections::HashMap;
+use std::error::Error;
+use std::io::Write;
+
+use junit_report::{Duration, ReportBuilder, TestCase, TestCaseBuilder, TestSuite, TestSuiteBuilder};
+use quick_xml::escape::escape;
+use quick_xml::events::{BytesDecl, BytesEnd, BytesStart, Event};
+use quick_xml::{Reader, Writer};
+use serde::Serialize;
+use time::OffsetDateTime;
+
+use crate::directives::RobotsLine;
+use crate::explain::Explanation;
+use crate::{crawl_delay_label, get_test_case_name, TestCaseOutput, TestRunSummary};
+
+#[derive(Serialize)]
+struct JsonTestResult<'a> {
+    user_agent: &'a str,
+    url: &'a str,
+    expected: bool,
+    actual: bool,
+    expected_crawl_delay: Option<f32>,
+    crawl_delay: Option<f32>,
+    passed: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    name: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    reason: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<&'a str>,
+}
+
+impl<'a> From<&'a TestCaseOutput> for JsonTestResult<'a> {
+    fn from(result: &'a TestCaseOutput) -> Self {
+        JsonTestResult {
+            user_agent: &result.user_agent,
+            url: &result.url,
+            expected: result.expected_result,
+            actual: result.actual_result(),
+            expected_crawl_delay: result.expected_crawl_delay,
+            crawl_delay: result.crawl_delay,
+            passed: result.passed(),
+            name: result.name.as_deref(),
+            reason: result.reason.as_deref(),
+            error: result.error.as_deref(),
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct JsonSummary {
+    total: usize,
+    passed: usize,
+    failed: usize,
+    errored: usize,
+    allow_mismatches: usize,
+    crawl_delay_mismatches: usize,
+    elapsed_ms: u128,
+}
+
+impl From<&TestRunSummary> for JsonSummary {
+    fn from(summary: &TestRunSummary) -> Self {
+        JsonSummary {
+            total: summary.total,
+            passed: summary.passed,
+            failed: summary.failed,
+            errored: summary.errored,
+            allow_mismatches: summary.allow_mismatches,
+            crawl_delay_mismatches: summary.crawl_delay_mismatches,
+            elapsed_ms: summary.elapsed.as_millis(),
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct JsonReport<'a> {
+    results: Vec<JsonTestResult<'a>>,
+    summary: JsonSummary,
+}
+
+/// Last line of an NDJSON stream, keyed so it can't be mistaken for a result
+#[derive(Serialize)]
+struct NdjsonSummary {
+    summary: JsonSummary,
+}
+
+/// Write the results and a summary as a single JSON object
+pub fn write_json(mut writer: impl Write, test_results: &[TestCaseOutput], summary: &TestRunSummary) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let report = JsonReport {
+        results: test_results.iter().map(JsonTestResult::from).collect(),
+        summary: JsonSummary::from(summary),
+    };
+
+    serde_json::to_writer_pretty(&mut writer, &report)?;
+    writeln!(writer)?;
+    Ok(())
+}
+
+/// Write just the summary as a JSON object
+pub fn write_json_summary(mut writer: impl Write, summary: &TestRunSummary) -> Result<(), Box<dyn Error + Send + Sync>> {
+    serde_json::to_writer_pretty(&mut writer, &JsonSummary::from(summary))?;
+    writeln!(writer)?;
+    Ok(())
+}
+
+#[derive(Serialize)]
+struct JsonAgentRules<'a> {
+    user_agent: &'a str,
+    group_user_agent: &'a str,
+    root_allowed: bool,
+    crawl_delay: Option<f32>,
+    rules: Vec<JsonRule<'a>>,
+}
+
+#[derive(Serialize)]
+struct JsonRule<'a> {
+    line_number: usize,
+    key: &'a str,
+    value: &'a str,
+}
+
+impl<'a> From<&RobotsLine<'a>> for JsonRule<'a> {
+    fn from(rule: &RobotsLine<'a>) -> Self {
+        JsonRule { line_number: rule.line_number, key: rule.key, value: rule.value }
+    }
+}
+
+/// Write a JSON array describing how the robots.txt applies to each user agent, from explanations
+/// of its root path
+pub fn write_json_agent_rules(mut writer: impl Write, explanations: &[(&str, Explanation)]) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let agents: Vec<JsonAgentRules> = explanations.iter()
+        .map(|(user_agent, explanation)| JsonAgentRules {
+            user_agent,
+            group_user_agent: &explanation.group_user_agent,
+            root_allowed: explanation.allowed,
+            crawl_delay: explanation.crawl_delay,
+            rules: explanation.rules.iter().map(JsonRule::from).collect(),
+        })
+        .collect();
+
+    serde_json::to_writer_pretty(&mut writer, &agents)?;
+    writeln!(writer)?;
+    Ok(())
+}
+
+#[derive(Serialize)]
+struct JsonExplanation<'a> {
+    url: &'a str,
+    user_agent: &'a str,
+    group_user_agent: &'a str,
+    allowed: bool,
+    matched_rule: Option<JsonRule<'a>>,
+    crawl_delay: Option<f32>,
+    applicable_rules: Vec<JsonRule<'a>>,
+}
+
+/// Write an explanation of the decision for a URL as a JSON object, with a `null` matched rule
+/// when none matches
+pub fn write_json_explanation(mut writer: impl Write, url: &str, user_agent: &str, explanation: &Explanation) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let explanation = JsonExplanation {
+        url,
+        user_agent,
+        group_user_agent: &explanation.group_user_agent,
+        allowed: explanation.allowed,
+        matched_rule: explanation.matching_rule.as_ref().map(JsonRule::from),
+        crawl_delay: explanation.crawl_delay,
+        applicable_rules: explanation.rules.iter().map(JsonRule::from).collect(),
+    };
+
+    serde_json::to_writer_pretty(&mut writer, &explanation)?;
+    writeln!(writer)?;
+    Ok(())
+}
+
+#[derive(Serialize)]
+struct JsonUrlDecision<'a> {
+    user_agent: &'a str,
+    url: &'a str,
+    allowed: bool,
+}
+
+/// Write a JSON array of whether each URL is allowed for the user agent
+pub fn write_json_url_decisions(mut writer: impl Write, user_agent: &str, decisions: &[(&str, bool)]) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let decisions: Vec<JsonUrlDecision> = decisions.iter()
+        .map(|&(url, allowed)| JsonUrlDecision { user_agent, url, allowed })
+        .collect();
+
+    serde_json::to_writer_pretty(&mut writer, &decisions)?;
+    writeln!(writer)?;
+    Ok(())
+}
+
+/// Write whether each URL is allowed for the user agent as CSV, in the columns of a test case
+/// file so it can be kept as one to catch changes to the decisions
+pub fn write_csv_url_decisions(writer: impl Write, user_agent: &str, decisions: &[(&str, bool)]) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let mut csv_writer = csv::Writer::from_writer(writer);
+    csv_writer.write_record(["user_agent", "url", "expected_result"])?;
+    for &(url, allowed) in decisions {
+        csv_writer.write_record([user_agent, url, if allowed { "true" } else { "false" }])?;
+    }
+    csv_writer.flush()?;
+    Ok(())
+}
+
+/// Write one JSON object per line for each result, in test case order, then a `{"summary": ...}` line
+pub fn write_ndjson(writer: &mut dyn Write, test_results: &[TestCaseOutput], summary: &TestRunSummary) -> Result<(), Box<dyn Error + Send + Sync>> {
+    for result in test_results {
+        serde_json::to_writer(&mut *writer, &JsonTestResult::from(result))?;
+        writeln!(writer)?;
+    }
+    serde_json::to_writer(&mut *writer, &NdjsonSummary { summary: JsonSummary::from(summary) })?;
+    writeln!(writer)?;
+    Ok(())
+}
+
+/// Write the results as a TAP version 13 stream, with a YAML diagnostic block for each failure
+pub fn write_tap(writer: &mut dyn Write, test_results: &[TestCaseOutput]) -> Result<(), Box<dyn Error + Send + Sync>> {
+    writeln!(writer, "TAP version 13")?;
+    writeln!(writer, "1..{}", test_results.len())?;
+
+    for (index, result) in test_results.iter().enumerate() {
+        // An unescaped `#` would start a TAP directive, and urls may well contain one
+        let description = get_test_case_name(result).replace('#', "\\#");
+        if result.passed() {
+            writeln!(writer, "ok {} - {}", index + 1, description)?;
+            continue;
+        }
+
+        writeln!(writer, "not ok {} - {}", index + 1, description)?;
+        writeln!(writer, "  ---")?;
+        if let Some(error) = &result.error {
+            writeln!(writer, "  error: {:?}", error)?;
+        } else if !result.result {
+            writeln!(writer, "  expected: {}", result.result_label(result.expected_result))?;
+            writeln!(writer, "  got: {}", result.result_label(result.actual_result()))?;
+        }
+        if !result.errored() && !result.crawl_delay_result() {
+            writeln!(writer, "  expected_crawl_delay: {}", crawl_delay_label(result.expected_crawl_delay))?;
+            writeln!(writer, "  crawl_delay: {}", crawl_delay_label(result.crawl_delay))?;
+        }
+        if let Some(reason) = &result.reason {
+            writeln!(writer, "  reason: {:?}", reason)?;
+        }
+        writeln!(writer, "  ...")?;
+    }
+    Ok(())
+}
+
+/// The key every format lists test cases by, their user agent and then url, so reports diff
+/// cleanly whatever order the results came in
+pub fn report_order(result: &TestCaseOutput) -> (&str, &str) {
+    (&result.user_agent, &result.url)
+}
+
+/// Write the results as a Markdown table followed by a summary line, sorted by user agent and URL
+pub fn write_markdown(writer: &mut dyn Write, test_results: &[TestCaseOutput], summary: &TestRunSummary) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let mut sorted: Vec<&TestCaseOutput> = test_results.iter().collect();
+    sorted.sort_by_key(|result| report_order(result));
+
+    writeln!(writer, "| User Agent | URL | Expected | Actual | Result |")?;
+    writeln!(writer, "| --- | --- | --- | --- | --- |")?;
+    for result in sorted {
+        let (expected, actual) = expectation_labels(result);
+        writeln!(
+            writer,
+            "| {} | {} | {} | {} | {} |",
+            escape_markdown_cell(&result.user_agent),
+            escape_markdown_cell(&result.url),
+            expected,
+            actual,
+            status_label(result, "\u{2705} pass", "\u{274c} fail", "\u{26a0}\u{fe0f} error")
+        )?;
+    }
+
+    writeln!(writer)?;
+    writeln!(writer, "**{} test cases, {} passed, {} failed{}**", summary.total, summary.passed, summary.failed, errored_label(summary))?;
+    Ok(())
+}
+
+/// The label for a test case that passed, failed or errored
+fn status_label<'a>(result: &TestCaseOutput, passed: &'a str, failed: &'a str, errored: &'a str) -> &'a str {
+    match (result.errored(), result.passed()) {
+        (true, _) => errored,
+        (false, true) => passed,
+        (false, false) => failed,
+    }
+}
+
+/// `, N errored` for a summary line, left out when nothing errored
+fn errored_label(summary: &TestRunSummary) -> String {
+    if summary.errored > 0 { format!(", {} errored", summary.errored) } else { String::new() }
+}
+
+fn escape_markdown_cell(value: &str) -> String {
+    value.replace('|', "\\|")
+}
+
+const HTML_STYLE: &str = "body{font-family:sans-serif;margin:2em}\
+header{padding:1em;border-radius:4px;color:#fff}\
+header.passed{background:#2e7d32}header.failed{background:#c62828}\
+table{border-collapse:collapse;margin-top:1em;width:100%}\
+th,td{border:1px solid #ccc;padding:.4em .6em;text-align:left}\
+th{background:#f0f0f0;cursor:pointer}tr.failed td{background:#fdecea}";
+
+// Sorts the table by the clicked column, toggling between ascending and descending
+const HTML_SCRIPT: &str = "document.querySelectorAll('th').forEach(function(th,column){\
+th.addEventListener('click',function(){\
+var body=document.querySelector('tbody'),ascending=th.dataset.order!=='asc';\
+th.parentNode.querySelectorAll('th').forEach(function(other){delete other.dataset.order;});\
+th.dataset.order=ascending?'asc':'desc';\
+Array.from(body.rows).sort(function(a,b){\
+var order=a.cells[column].textContent.localeCompare(b.cells[column].textContent);\
+return ascending?order:-order;}).forEach(function(row){body.appendChild(row);});});});";
+
+/// Write the results as a self-contained HTML page with a sortable table, sorted by user agent and URL
+///
+/// `source` is the robots.txt the results are for, shown in the page header.
+pub fn write_html(writer: &mut dyn Write, test_results: &[TestCaseOutput], summary: &TestRunSummary, source: &str) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let mut sorted: Vec<&TestCaseOutput> = test_results.iter().collect();
+    sorted.sort_by_key(|result| report_order(result));
+
+    writeln!(writer, "<!DOCTYPE html>")?;
+    writeln!(writer, "<html lang=\"en\">")?;
+    writeln!(writer, "<head>")?;
+    writeln!(writer, "<meta charset=\"utf-8\">")?;
+    writeln!(writer, "<title>robots.txt test results for {}</title>", escape_html(source))?;
+    writeln!(writer, "<style>{}</style>", HTML_STYLE)?;
+    writeln!(writer, "</head>")?;
+    writeln!(writer, "<body>")?;
+    writeln!(writer, "<header class=\"{}\">", if summary.failed == 0 { "passed" } else { "failed" })?;
+    writeln!(writer, "<h1>robots.txt test results</h1>")?;
+    writeln!(writer, "<p>{}</p>", escape_html(source))?;
+    writeln!(writer, "<p>{} test cases, {} passed, {} failed{}</p>", summary.total, summary.passed, summary.failed, errored_label(summary))?;
+    writeln!(writer, "</header>")?;
+    writeln!(writer, "<table>")?;
+    writeln!(writer, "<thead><tr><th>Result</th><th>User Agent</th><th>URL</th><th>Expected</th><th>Actual</th></tr></thead>")?;
+    writeln!(writer, "<tbody>")?;
+    for result in sorted {
+        let (expected, actual) = expectation_labels(result);
+        writeln!(
+            writer,
+            "<tr class=\"{}\"><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>",
+            if result.passed() { "passed" } else { "failed" },
+            status_label(result, "pass", "fail", "error"),
+            escape_html(&result.user_agent),
+            escape_html(&result.url),
+            expected,
+            actual
+        )?;
+    }
+    writeln!(writer, "</tbody>")?;
+    writeln!(writer, "</table>")?;
+    writeln!(writer, "<script>{}</script>", HTML_SCRIPT)?;
+    writeln!(writer, "</body>")?;
+    writeln!(writer, "</html>")?;
+    Ok(())
+}
+
+fn escape_html(value: &str) -> String {
+    value.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Expected and actual labels of a test case for tabular reports, covering the crawl-delay too
+fn expectation_labels(result: &TestCaseOutput) -> (String, String) {
+    if let Some(error) = &result.error {
+        return (result.result_label(result.expected_result).to_string(), error.clone());
+    }
+    match (result.checks_url(), result.expected_crawl_delay) {
+        (true, None) => (
+            result.result_label(result.expected_result).to_string(),
+            result.result_label(result.actual_result()).to_string(),
+        ),
+        (true, Some(_)) => (
+            format!("{}, crawl-delay {}", result.result_label(result.expected_result), crawl_delay_label(result.expected_crawl_delay)),
+            format!("{}, crawl-delay {}", result.result_label(result.actual_result()), crawl_delay_label(result.crawl_delay)),
+        ),
+        (false, _) => (
+            format!("crawl-delay {}", crawl_delay_label(result.expected_crawl_delay)),
+            format!("crawl-delay {}", crawl_delay_label(result.crawl_delay)),
+        ),
+    }
+}
+
+/// Write a GitHub Actions `::error` workflow command for each failing test case
+///
+/// `file` is the test case file the annotations point at, `None` when it was read from stdin.
+pub fn write_github_annotations(writer: &mut dyn Write, test_results: &[TestCaseOutput], file: Option<&str>) -> Result<(), Box<dyn Error + Send + Sync>> {
+    for result in test_results.iter().filter(|n| !n.passed()) {
+        let mut properties = Vec::new();
+        if let Some(file) = file {
+            properties.push(format!("file={}", escape_github_property(file)));
+            if let Some(line_number) = result.line_number {
+                properties.push(format!("line={}", line_number));
+            }
+        }
+        properties.push("title=robots.txt test failed".to_string());
+
+        let mut messages = Vec::new();
+        if let Some(error) = &result.error {
+            messages.push(error.clone());
+        } else if !result.result {
+            let subject = if result.is_sitemap_assertion() {
+                format!("Sitemap {}", result.url)
+            } else if result.is_host_assertion() {
+                format!("Host {}", result.url)
+            } else {
+                format!("Accessing {} as {}", result.url, result.user_agent)
+            };
+            messages.push(format!(
+                "{} expected {} got {}",
+                subject,
+                result.result_label(result.expected_result),
+                result.result_label(result.actual_result())
+            ));
+        }
+        if !result.errored() && !result.crawl_delay_result() {
+            messages.push(format!(
+                "Crawl-delay for {} expected {} got {}",
+                result.user_agent,
+                crawl_delay_label(result.expected_crawl_delay),
+                crawl_delay_label(result.crawl_delay)
+            ));
+        }
+
+        let mut message = messages.join(", ");
+        if let Some(reason) = &result.reason {
+            message = format!("{} ({})", message, reason);
+        }
+        writeln!(writer, "::error {}::{}", properties.join(","), escape_github_data(&message))?;
+    }
+    Ok(())
+}
+
+fn escape_github_data(value: &str) -> String {
+    value.replace('%', "%25").replace('\r', "%0D").replace('\n', "%0A")
+}
+
+fn escape_github_property(value: &str) -> String {
+    escape_github_data(value).replace(':', "%3A").replace(',', "%2C")
+}
+
+/// A test suite of a JUnit XML report
+#[derive(Debug, Clone, Copy)]
+pub struct JunitSuite<'a> {
+    pub name: &'a str,
+    /// written as a `description` property of the suite
+    pub description: Option<&'a str>,
+    pub results: &'a [TestCaseOutput],
+    /// when the run the suite is part of started
+    pub timestamp: OffsetDateTime,
+    /// wall-clock time running the suite took, which is reported instead of the sum of its test
+    /// case durations
+    pub elapsed: std::time::Duration,
+}
+
+/// Write a JUnit XML report with one test suite per [`JunitSuite`]
+pub fn write_junit_suites(mut writer: impl Write, suites: &[JunitSuite]) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let r = ReportBuilder::new()
+        .add_testsuites(suites.iter().map(|suite| build_test_suite(suite, false)))
+        .build();
+
+    let mut xml = Vec::new();
+    r.write_xml(&mut xml)?;
+    let xml = String::from_utf8(xml)?;
+
+    writer.write_all(add_suite_descriptions(&set_suite_times(&xml, suites), suites).as_bytes())?;
+    Ok(())
+}
+
+/// Write a JUnit XML report like [`write_junit_suites`], leaving out the passing test cases
+///
+/// The `tests` count on each test suite still covers every test case that was run.
+pub fn write_junit_failures(mut writer: impl Write, suites: &[JunitSuite]) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let r = ReportBuilder::new()
+        .add_testsuites(suites.iter().map(|suite| build_test_suite(suite, true)))
+        .build();
+
+    let mut xml = Vec::new();
+    r.write_xml(&mut xml)?;
+    let xml = String::from_utf8(xml)?;
+
+    // junit-report counts the test cases it was given, so put the real totals back
+    let totals: Vec<String> = suites.iter().map(|suite| suite.results.len().to_string()).collect();
+    let xml = set_suite_attribute(&xml, "tests", &totals);
+    writer.write_all(add_suite_descriptions(&set_suite_times(&xml, suites), suites).as_bytes())?;
+    Ok(())
+}
+
+/// Write a JUnit XML report holding the test suites of both reports, `existing` ones first
+///
+/// The suites are numbered again, and their `tests`, `failures` and `errors` counts are totalled
+/// on the `<testsuites>` element.
+pub fn merge_junit_reports(writer: impl Write, existing: &str, new: &str) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let mut events = read_test_suites(existing)?;
+    events.extend(read_test_suites(new)?);
+
+    // Only the suites themselves hold counts, not the test cases inside them
+    let mut totals = [("tests", 0), ("failures", 0), ("errors", 0)];
+    let mut depth = 0;
+    for event in &events {
+        match event {
+            Event::Start(suite) | Event::Empty(suite) if depth == 0 => {
+                for (name, total) in &mut totals {
+                    if let Some(count) = suite.try_get_attribute(*name)? {
+                        *total += count.unescape_value()?.parse::<usize>().unwrap_or(0);
+                    }
+                }
+            },
+            _ => {},
+        }
+        match event {
+            Event::Start(_) => depth += 1,
+            Event::End(_) => depth -= 1,
+            _ => {},
+        }
+    }
+
+    let mut xml = Writer::new(writer);
+    xml.write_event(Event::Decl(BytesDecl::new("1.0", Some("utf-8"), None)))?;
+    let totals: Vec<(&str, String)> = totals.iter().map(|(name, total)| (*name, total.to_string())).collect();
+    xml.write_event(Event::Start(BytesStart::new("testsuites").with_attributes(totals.iter().map(|(name, total)| (*name, total.as_str())))))?;
+    let mut depth = 0;
+    let mut id = 0;
+    for event in events {
+        let event = match event {
+            Event::Start(suite) if depth == 0 => Event::Start(renumber_test_suite(&suite, id)?),
+            Event::Empty(suite) if depth == 0 => Event::Empty(renumber_test_suite(&suite, id)?),
+            event => event,
+        };
+        match event {
+            Event::Start(_) if depth == 0 => { depth += 1; id += 1; },
+            Event::Empty(_) if depth == 0 => id += 1,
+            Event::Start(_) => depth += 1,
+            Event::End(_) => depth -= 1,
+            _ => {},
+        }
+        xml.write_event(event)?;
+    }
+    xml.write_event(Event::End(BytesEnd::new("testsuites")))?;
+    Ok(())
+}
+
+/// Whether each test case of a JUnit report passed, keyed by test case name
+///
+/// A test case with a `<failure>` or `<error>` failed, any other passed.
+pub fn read_junit_outcomes(xml: &str) -> Result<HashMap<String, bool>, Box<dyn Error + Send + Sync>> {
+    let mut reader = Reader::from_str(xml);
+    let mut outcomes = HashMap::new();
+    let mut test_case: Option<(String, bool)> = None;
+
+    loop {
+        match reader.read_event()? {
+            Event::Eof => break,
+            Event::Empty(element) if element.name().as_ref() == b"testcase" => {
+                outcomes.insert(test_case_name(&element)?, true);
+            },
+            Event::Start(element) if element.name().as_ref() == b"testcase" => {
+                test_case = Some((test_case_name(&element)?, true));
+            },
+            Event::Start(element) | Event::Empty(element) if matches!(element.name().as_ref(), b"failure" | b"error") => {
+                if let Some((_, passed)) = &mut test_case {
+                    *passed = false;
+                }
+            },
+            Event::End(element) if element.name().as_ref() == b"testcase" => {
+                if let Some((name, passed)) = test_case.take() {
+                    outcomes.insert(name, passed);
+                }
+            },
+            _ => {},
+        }
+    }
+    Ok(outcomes)
+}
+
+fn test_case_name(test_case: &BytesStart) -> Result<String, Box<dyn Error + Send + Sync>> {
+    match test_case.try_get_attribute("name")? {
+        Some(name) => Ok(name.unescape_value()?.into_owned()),
+        None => Err("expected a name on every <testcase> element".into()),
+    }
+}
+
+/// Test cases whose outcome changed since an earlier report
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReportDelta<'a> {
+    /// failing now but passing in the earlier report, or not in it at all
+    pub newly_failing: Vec<&'a TestCaseOutput>,
+    /// passing now but failing in the earlier report
+    pub newly_passing: Vec<&'a TestCaseOutput>,
+}
+
+/// Compare results with the outcomes of an earlier report read by [`read_junit_outcomes`],
+/// matching test cases by name
+pub fn diff_against_report<'a>(previous: &HashMap<String, bool>, test_results: &'a [TestCaseOutput]) -> ReportDelta<'a> {
+    let mut delta = ReportDelta { newly_failing: Vec::new(), newly_passing: Vec::new() };
+    for result in test_results {
+        match (result.passed(), previous.get(&get_test_case_name(result))) {
+            (false, Some(true) | None) => delta.newly_failing.push(result),
+            (true, Some(false)) => delta.newly_passing.push(result),
+            _ => {},
+        }
+    }
+    delta
+}
+
+/// The events inside a report's `<testsuites>` element
+fn read_test_suites(xml: &str) -> Result<Vec<Event<'static>>, Box<dyn Error + Send + Sync>> {
+    let mut reader = Reader::from_str(xml);
+    reader.trim_text(true);
+    let mut events = Vec::new();
+    let mut depth = 0;
+
+    loop {
+        let event = reader.read_event()?;
+        match &event {
+            Event::Eof => break,
+            Event::Start(element) if depth == 0 && element.name().as_ref() != b"testsuites" => {
+                return Err("expected a JUnit report with a <testsuites> element".into());
+            },
+            Event::Start(_) => depth += 1,
+            Event::End(_) => depth -= 1,
+            _ => {},
+        }
+        // The <testsuites> element itself is written again with the totals
+        let inside = match &event {
+            Event::Start(_) => depth > 1,
+            Event::End(_) => depth > 0,
+            Event::Decl(_) => false,
+            _ => depth > 0,
+        };
+        if inside {
+            events.push(event.into_owned());
+        }
+    }
+    Ok(events)
+}
+
+/// A copy of a `<testsuite>` start tag with its `id` attribute set to `id`
+fn renumber_test_suite(suite: &BytesStart, id: usize) -> Result<BytesStart<'static>, Box<dyn Error + Send + Sync>> {
+    let mut renumbered = BytesStart::new(String::from_utf8(suite.name().as_ref().to_vec())?);
+    let id = id.to_string();
+    for attribute in suite.attributes() {
+        let attribute = attribute?;
+        if attribute.key.as_ref() == b"id" {
+            renumbered.push_attribute(("id", id.as_str()));
+        } else {
+            renumbered.push_attribute(attribute);
+        }
+    }
+    Ok(renumbered)
+}
+
+/// Report each suite's wall-clock time, as junit-report sums the test case durations
+fn set_suite_times(xml: &str, suites: &[JunitSuite]) -> String {
+    let times: Vec<String> = suites.iter().map(|suite| suite.elapsed.as_secs_f64().to_string()).collect();
+    set_suite_attribute(xml, "time", &times)
+}
+
+/// Give each suite with a description a `<properties>` element holding it, as junit-report has no
+/// support for suite properties
+fn add_suite_descriptions(xml: &str, suites: &[JunitSuite]) -> String {
+    let mut output = String::with_capacity(xml.len());
+    let mut rest = xml;
+
+    for suite in suites {
+        let Some(suite_start) = rest.find("<testsuite ") else { break };
+        let Some(tag_len) = rest[suite_start..].find('>') else { break };
+        let tag_end = suite_start + tag_len;
+        let Some(description) = suite.description else {
+            output.push_str(&rest[..=tag_end]);
+            rest = &rest[tag_end + 1..];
+            continue;
+        };
+
+        let properties = format!("<properties><property name=\"description\" value=\"{}\"/></properties>", escape(description));
+        // A suite without test cases is an empty element, which now needs a closing tag
+        match rest[..tag_end].strip_suffix('/') {
+            Some(start_tag) => {
+                output.push_str(start_tag);
+                output.push('>');
+                output.push_str(&properties);
+                output.push_str("</testsuite>");
+            },
+            None => {
+                output.push_str(&rest[..=tag_end]);
+                output.push_str(&properties);
+            },
+        }
+        rest = &rest[tag_end + 1..];
+    }
+    output.push_str(rest);
+    output
+}
+
+/// Replace an attribute of each `<testsuite>` element in turn
+fn set_suite_attribute(xml: &str, attribute: &str, values: &[String]) -> String {
+    let attribute = format!(" {}=\"", attribute);
+    let mut output = String::with_capacity(xml.len());
+    let mut rest = xml;
+
+    for value in values {
+        let Some(suite_start) = rest.find("<testsuite ") else { break };
+        let Some(attribute_start) = rest[suite_start..].find(&attribute) else { break };
+        let value_start = suite_start + attribute_start + attribute.len();
+        let Some(value_len) = rest[value_start..].find('"') else { break };
+
+        output.push_str(&rest[..value_start]);
+        output.push_str(value);
+        rest = &rest[value_start + value_len..];
+    }
+    output.push_str(rest);
+    output
+}
+
+fn build_test_suite(suite: &JunitSuite, only_failures: bool) -> TestSuite {
+    let mut test_cases: Vec<TestCase> = Vec::new();
+    let mut test_results: Vec<&TestCaseOutput> = suite.results.iter()
+        .filter(|result| !only_failures || !result.passed())
+        .collect();
+    test_results.sort_by_key(|result| report_order(result));
+
+    for result in test_results {
+        let test_case_name = get_test_case_name(result);
+        let duration = Duration::nanoseconds(i64::try_from(result.duration.as_nanos()).unwrap_or(i64::MAX));
+
+        let crawl_delay_message = format!(
+            "crawl-delay not equal: expected {} got {}",
+            crawl_delay_label(result.expected_crawl_delay),
+            crawl_delay_label(result.crawl_delay)
+        );
+
+        // The reason the test case gives for its expectations explains the failure better than
+        // the assertion alone
+        let failure_message = |message: &str| match &result.reason {
+            Some(reason) => format!("{} ({})", message, reason),
+            None => message.to_string(),
+        };
+
+        let mut test_case = match (result.result, result.crawl_delay_result()) {
+            _ if result.errored() => TestCase::error(&test_case_name, duration, "parse", result.error.as_deref().unwrap_or_default()),
+            (true, true) => TestCaseBuilder::success(&test_case_name, duration).build(),
+            (false, true) => TestCase::failure(&test_case_name, duration, "assert_eq", &failure_message("not equal")),
+            (false, false) => TestCase::failure(
+                &test_case_name,
+                duration,
+                "assert_eq",
+                &failure_message(&format!("not equal, {}", crawl_delay_message)),
+            ),
+            (true, false) => TestCase::failure(&test_case_name, duration, "crawl_delay", &failure_message(&crawl_delay_message)),
+        };
+        // CI dashboards group test cases by class, giving a pass rate per bot
+        test_case.set_classname(&result.user_agent);
+        test_cases.push(test_case);
+    }
+
+    TestSuiteBuilder::new(suite.name)
+        .set_timestamp(suite.timestamp)
+        .add_testcases(test_cases)
+        .build()
+}
@@ -0,0 +1,88 @@
+//! Test cases generated from the robots.txt's own rules, checking each rule does what it says
+use crate::directives::{parse_lines, DirectiveKind, ParsedLine};
+use crate::{TestCaseDefinition, WILDCARD_USER_AGENT};
+
+/// Generate one test case per `Allow` or `Disallow` rule for each user agent of its group,
+/// expecting the URL built from the rule's path to be decided the way the rule declares
+///
+/// Each `*` in the path becomes `x` and a trailing `$` is dropped, so the URL is one the path
+/// matches. A test case failing means a longer rule, or the pattern syntax, decides otherwise.
+/// The line number of each test case is that of its rule in the robots.txt.
+pub fn generate_test_cases(robots: &str) -> Vec<TestCaseDefinition> {
+    let mut test_cases = Vec::new();
+    // Rules before the first User-agent line only apply, to everyone, when there is no such
+    // line at all. Otherwise the matcher ignores them, which lint warns about
+    let has_user_agents = parse_lines(robots)
+        .any(|line| matches!(line, ParsedLine::Directive(directive) if directive.kind() == Some(DirectiveKind::UserAgent)));
+    let mut user_agents = if has_user_agents { Vec::new() } else { vec![WILDCARD_USER_AGENT] };
+    let mut in_user_agents = false;
+
+    for line in parse_lines(robots) {
+        let ParsedLine::Directive(directive) = line else { continue };
+        match directive.kind() {
+            Some(DirectiveKind::UserAgent) => {
+                // Consecutive User-agent lines share the group that follows them
+                if !in_user_agents {
+                    user_agents.clear();
+                }
+                in_user_agents = true;
+                user_agents.push(directive.value);
+            },
+            Some(kind @ (DirectiveKind::Allow | DirectiveKind::Disallow)) => {
+                in_user_agents = false;
+                // An empty rule matches nothing
+                if directive.value.is_empty() {
+                    continue;
+                }
+                let url = directive.value.strip_suffix('$').unwrap_or(directive.value).replace('*', "x");
+                for user_agent in &user_agents {
+                    test_cases.push(TestCaseDefinition {
+                        user_agent: user_agent.to_string(),
+                        url: url.clone(),
+                        expected_result: kind == DirectiveKind::Allow,
+                        expected_crawl_delay: None,
+                        line_number: Some(directive.line_number as u64),
+                        name: None,
+                        reason: None,
+                    });
+                }
+            },
+            Some(DirectiveKind::CrawlDelay) => in_user_agents = false,
+            _ => {},
+        }
+    }
+    test_cases
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::run_tests;
+
+    #[test]
+    fn rules_before_the_first_user_agent_are_skipped_when_there_are_groups() {
+        let robots = "Disallow: /early\nUser-agent: googlebot\nDisallow: /g\n";
+        let test_cases = generate_test_cases(robots);
+        assert_eq!(test_cases.len(), 1);
+        assert_eq!(test_cases[0].user_agent, "googlebot");
+        assert_eq!(test_cases[0].url, "/g");
+        assert_eq!(run_tests(robots.as_bytes(), &test_cases).summary.failed, 0);
+    }
+
+    #[test]
+    fn rules_without_any_user_agent_apply_to_everyone() {
+        let test_cases = generate_test_cases("Disallow: /early\n");
+        assert_eq!(test_cases.len(), 1);
+        assert_eq!(test_cases[0].user_agent, WILDCARD_USER_AGENT);
+        assert!(!test_cases[0].expected_result);
+    }
+
+    #[test]
+    fn consecutive_user_agents_share_a_group() {
+        let test_cases = generate_test_cases("User-agent: a\nUser-agent: b\nAllow: /x*y$\n");
+        let user_agents: Vec<&str> = test_cases.iter().map(|test| test.user_agent.as_str()).collect();
+        assert_eq!(user_agents, ["a", "b"]);
+        assert_eq!(test_cases[0].url, "/xxy");
+        assert!(test_cases[0].expected_result);
+    }
+}
@@ -0,0 +1,641 @@
+//! Loading test case definitions from CSV, JSON and YAML files
+use std::collections::HashMap;
+use std::error::Error;
+use std::io::Read;
+use std::path::Path;
+
+use clap::ValueEnum;
+use lenient_bool::LenientBool;
+use serde::Deserialize;
+
+use crate::TestCaseDefinition;
+
+/// Format of a test case file
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum TestCaseSource {
+    /// comma (or otherwise) separated `user_agent,url,expected_result` rows
+    Csv,
+    /// array of `{user_agent, url, expected_result}` objects, or a `{description, cases}` object
+    /// holding one
+    Json,
+    /// list of `{user_agent, url, expected_result}` mappings, or a `{description, cases}` mapping
+    /// holding one
+    Yaml,
+}
+
+impl TestCaseSource {
+    /// Detect the format from a file extension, if it is a known one
+    pub fn from_path(path: &Path) -> Option<TestCaseSource> {
+        match path.extension()?.to_str()?.to_ascii_lowercase().as_str() {
+            "csv" | "tsv" => Some(TestCaseSource::Csv),
+            "json" => Some(TestCaseSource::Json),
+            "yaml" | "yml" => Some(TestCaseSource::Yaml),
+            _ => None,
+        }
+    }
+
+    /// Guess the format from the start of the content, for input without a file name
+    ///
+    /// Content starting with `[` or `{` is JSON, with a `---` document marker or a `- ` list item
+    /// it is YAML, and anything else is CSV. Fails for content starting with a `#` comment, which
+    /// both CSV and YAML allow.
+    pub fn sniff(content: &[u8]) -> Result<TestCaseSource, Box<dyn Error>> {
+        let content = content.trim_ascii_start();
+        match content.first() {
+            Some(b'[' | b'{') => Ok(TestCaseSource::Json),
+            Some(b'-') if content.starts_with(b"---") || content.starts_with(b"- ") || content.starts_with(b"-\n") => Ok(TestCaseSource::Yaml),
+            Some(b'#') => Err("unable to tell whether the test cases are CSV or YAML, as both allow # comments, pass --test-format".into()),
+            _ => Ok(TestCaseSource::Csv),
+        }
+    }
+}
+
+/// Options for reading CSV test case files
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CsvOptions {
+    /// whether the first row names the columns, `true` by default
+    pub has_headers: bool,
+    /// field delimiter, `,` by default
+    pub delimiter: u8,
+    /// user agent for rows that leave theirs blank, or for files without a user_agent column
+    pub default_user_agent: Option<String>,
+}
+
+impl Default for CsvOptions {
+    fn default() -> Self {
+        CsvOptions { has_headers: true, delimiter: b',', default_user_agent: None }
+    }
+}
+
+impl CsvOptions {
+    /// The default options for a file, reading `.tsv` files as tab separated
+    pub fn from_path(path: &Path) -> CsvOptions {
+        let is_tsv = path.extension()
+            .and_then(|extension| extension.to_str())
+            .is_some_and(|extension| extension.eq_ignore_ascii_case("tsv"));
+        CsvOptions {
+            delimiter: if is_tsv { b'\t' } else { b',' },
+            ..CsvOptions::default()
+        }
+    }
+}
+
+/// The test cases of a file, with the description of what they test if the file gives one
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct TestCaseFile {
+    /// only the JSON and YAML formats can describe their test cases
+    pub description: Option<String>,
+    pub test_cases: Vec<TestCaseDefinition>,
+}
+
+/// Load test cases in the given format, `csv_options` only apply to CSV
+///
+/// Like every loader, a test case for a list of user agents such as `googlebot|bingbot` becomes
+/// one test case per user agent, see [`expand_user_agents`].
+pub fn load_test_cases(reader: impl Read, source: TestCaseSource, csv_options: &CsvOptions) -> Result<Vec<TestCaseDefinition>, Box<dyn Error>> {
+    Ok(load_test_case_file(reader, source, csv_options)?.test_cases)
+}
+
+/// Load test cases in the given format like [`load_test_cases`], keeping the file's description
+pub fn load_test_case_file(reader: impl Read, source: TestCaseSource, csv_options: &CsvOptions) -> Result<TestCaseFile, Box<dyn Error>> {
+    match source {
+        TestCaseSource::Csv => Ok(TestCaseFile {
+            description: None,
+            test_cases: load_test_cases_from_csv_with_options(reader, csv_options)?,
+        }),
+        TestCaseSource::Json => from_structured_file(serde_json::from_reader(reader)?),
+        TestCaseSource::Yaml => from_structured_file(serde_yaml::from_reader(reader)?),
+    }
+}
+
+/// Load test cases from CSV with the columns `user_agent,url,expected_result[,expected_crawl_delay[,name[,reason]]]`
+/// and a header row
+pub fn load_test_cases_from_csv(reader: impl Read) -> Result<Vec<TestCaseDefinition>, Box<dyn Error>> {
+    load_test_cases_from_csv_with_options(reader, &CsvOptions::default())
+}
+
+/// Load test cases from CSV
+///
+/// With a header row, columns are looked up by their `user_agent`, `url`, `expected_result`,
+/// `expected_crawl_delay`, `name` and `reason` (or `note`) names, so they may come in any order.
+/// A first row that uses none of the first four names isn't a header but the first test case,
+/// and like the rows of a file without a header its columns are read in that order.
+///
+/// A row may leave `url` (and `expected_result`) blank to only assert the crawl-delay, and may
+/// leave `user_agent` blank to use the default user agent from the options. Blank lines and `#`
+/// comment lines are skipped. Errors point at the offending line and column.
+pub fn load_test_cases_from_csv_with_options(mut reader: impl Read, options: &CsvOptions) -> Result<Vec<TestCaseDefinition>, Box<dyn Error>> {
+    let mut content = Vec::new();
+    reader.read_to_end(&mut content)?;
+    // The reader counts lines from where it was before skipping comments, so they are dropped first
+    let lines = strip_comment_lines(&content);
+    let get_line = |record: &csv::StringRecord| lines.line_number(record);
+
+    let mut test_cases: Vec<TestCaseDefinition> = Vec::new();
+    let mut rdr = csv::ReaderBuilder::new()
+        .flexible(true)
+        .has_headers(options.has_headers)
+        .delimiter(options.delimiter)
+        .from_reader(lines.content.as_slice());
+
+    let (columns, first_row) = match options.has_headers {
+        true => {
+            let headers = rdr.headers()?;
+            match CsvColumns::from_headers(headers, options.default_user_agent.is_some())? {
+                Some(columns) => (columns, None),
+                None => (CsvColumns::POSITIONAL, Some(headers.clone())),
+            }
+        },
+        false => (CsvColumns::POSITIONAL, None),
+    };
+
+    for result in first_row.map(Ok).into_iter().chain(rdr.records()) {
+        let record = result?;
+        if is_blank_or_comment(&record) {
+            continue;
+        }
+
+        let url = get_optional_field(&record, columns.url).map_or("", |(_, url)| url);
+        let expected_crawl_delay = match get_optional_field(&record, columns.expected_crawl_delay).filter(|(_, value)| !value.is_empty()) {
+            Some((column, value)) => match parse_crawl_delay(value) {
+                Some(expected_crawl_delay) => Some(expected_crawl_delay),
+                None => return Err(format!(
+                    "line {}, column {}: invalid expected_crawl_delay {:?}, expected a number of seconds",
+                    get_line(&record),
+                    column + 1,
+                    value
+                ).into()),
+            },
+            None => None,
+        };
+        if url.is_empty() && expected_crawl_delay.is_none() {
+            return Err(format!("line {}: a test case needs a url or an expected_crawl_delay", get_line(&record)).into());
+        }
+
+        let expected_result = match get_optional_field(&record, columns.expected_result) {
+            Some((_, "")) | None if url.is_empty() => true,
+            Some((column, expected_result)) => match parse_expected_result(expected_result) {
+                Some(expected_result) => expected_result,
+                None => return Err(format!(
+                    "line {}, column {}: invalid expected_result {:?}, expected allow, disallow or a boolean such as true or false",
+                    get_line(&record),
+                    column + 1,
+                    expected_result
+                ).into()),
+            },
+            None => return Err(format!("line {}: missing expected_result field", get_line(&record)).into()),
+        };
+
+        let user_agent = match get_optional_field(&record, columns.user_agent).filter(|(_, value)| !value.trim().is_empty()) {
+            Some((_, user_agent)) => user_agent,
+            None => match options.default_user_agent.as_deref() {
+                Some(default_user_agent) => default_user_agent,
+                None => return Err(format!("line {}: missing user_agent field and no default user agent", get_line(&record)).into()),
+            },
+        };
+
+        let test_case = TestCaseDefinition {
+            user_agent: user_agent.to_string(),
+            url: url.to_string(),
+            expected_result,
+            expected_crawl_delay,
+            line_number: Some(get_line(&record)),
+            name: get_optional_field(&record, columns.name)
+                .map(|(_, name)| name.trim())
+                .filter(|name| !name.is_empty())
+                .map(str::to_string),
+            reason: get_optional_field(&record, columns.reason)
+                .map(|(_, reason)| reason.trim())
+                .filter(|reason| !reason.is_empty())
+                .map(str::to_string),
+        };
+
+        test_cases.push(test_case);
+    }
+    Ok(expand_user_agents(test_cases))
+}
+
+/// Where each test case field is found in a CSV row
+struct CsvColumns {
+    user_agent: Option<usize>,
+    url: Option<usize>,
+    expected_result: Option<usize>,
+    expected_crawl_delay: Option<usize>,
+    name: Option<usize>,
+    reason: Option<usize>,
+}
+
+impl CsvColumns {
+    const POSITIONAL: CsvColumns = CsvColumns {
+        user_agent: Some(0),
+        url: Some(1),
+        expected_result: Some(2),
+        expected_crawl_delay: Some(3),
+        name: Some(4),
+        reason: Some(5),
+    };
+
+    /// `None` when the row names none of the first four columns, so is a test case rather than a
+    /// header. Without a default user agent the header has to name a user_agent column
+    fn from_headers(headers: &csv::StringRecord, has_default_user_agent: bool) -> Result<Option<CsvColumns>, Box<dyn Error>> {
+        let find = |name: &str| headers.iter().position(|header| header.trim().eq_ignore_ascii_case(name));
+        let url = find("url");
+        let expected_result = find("expected_result");
+        let expected_crawl_delay = find("expected_crawl_delay");
+        let name = find("name");
+        let reason = find("reason").or_else(|| find("note"));
+
+        match find("user_agent") {
+            Some(user_agent) => Ok(Some(CsvColumns { user_agent: Some(user_agent), url, expected_result, expected_crawl_delay, name, reason })),
+            None if url.is_none() && expected_result.is_none() && expected_crawl_delay.is_none() => Ok(None),
+            None if has_default_user_agent => Ok(Some(CsvColumns { user_agent: None, url, expected_result, expected_crawl_delay, name, reason })),
+            None => Err("header: missing user_agent column and no default user agent".into()),
+        }
+    }
+}
+
+/// Load test cases from a JSON array of `{user_agent, url, expected_result}` objects, or from
+/// the `cases` of a `{description, cases}` object
+pub fn load_test_cases_from_json(reader: impl Read) -> Result<Vec<TestCaseDefinition>, Box<dyn Error>> {
+    Ok(from_structured_file(serde_json::from_reader(reader)?)?.test_cases)
+}
+
+/// Load test cases from a YAML list of `{user_agent, url, expected_result}` mappings, or from
+/// the `cases` of a `{description, cases}` mapping
+pub fn load_test_cases_from_yaml(reader: impl Read) -> Result<Vec<TestCaseDefinition>, Box<dyn Error>> {
+    Ok(from_structured_file(serde_yaml::from_reader(reader)?)?.test_cases)
+}
+
+/// A bare list of test cases, or one documented with a description
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum StructuredTestCaseFile {
+    Cases(Vec<StructuredTestCase>),
+    Described {
+        description: Option<String>,
+        cases: Vec<StructuredTestCase>,
+    },
+}
+
+#[derive(Deserialize)]
+struct StructuredTestCase {
+    user_agent: String,
+    #[serde(default)]
+    url: String,
+    expected_result: Option<ExpectedResult>,
+    expected_crawl_delay: Option<f32>,
+    name: Option<String>,
+    #[serde(alias = "note")]
+    reason: Option<String>,
+}
+
+/// Structured formats accept real booleans as well as the lenient strings CSV allows
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum ExpectedResult {
+    Bool(bool),
+    Text(String),
+}
+
+fn from_structured_file(file: StructuredTestCaseFile) -> Result<TestCaseFile, Box<dyn Error>> {
+    let (description, test_cases) = match file {
+        StructuredTestCaseFile::Cases(test_cases) => (None, test_cases),
+        StructuredTestCaseFile::Described { description, cases } => (description, cases),
+    };
+    Ok(TestCaseFile { description, test_cases: expand_user_agents(from_structured(test_cases)?) })
+}
+
+fn from_structured(test_cases: Vec<StructuredTestCase>) -> Result<Vec<TestCaseDefinition>, Box<dyn Error>> {
+    test_cases.into_iter()
+        .enumerate()
+        .map(|(index, test_case)| {
+            let expected_result = match test_case.expected_result {
+                Some(ExpectedResult::Bool(expected_result)) => expected_result,
+                Some(ExpectedResult::Text(text)) => match parse_expected_result(&text) {
+                    Some(expected_result) => expected_result,
+                    None => return Err(format!(
+                        "test case {}: invalid expected_result {:?}, expected allow, disallow or a boolean such as true or false",
+                        index + 1,
+                        text
+                    ).into()),
+                },
+                None if test_case.url.is_empty() => true,
+                None => return Err(format!("test case {}: missing expected_result", index + 1).into()),
+            };
+            if test_case.url.is_empty() && test_case.expected_crawl_delay.is_none() {
+                return Err(format!("test case {}: a test case needs a url or an expected_crawl_delay", index + 1).into());
+            }
+
+            Ok(TestCaseDefinition {
+                user_agent: test_case.user_agent,
+                url: test_case.url,
+                expected_result,
+                expected_crawl_delay: test_case.expected_crawl_delay,
+                line_number: None,
+                name: test_case.name,
+                reason: test_case.reason,
+            })
+        })
+        .collect()
+}
+
+/// Drop test cases that repeat an earlier one, returning the remaining cases and how many were dropped
+///
+/// Fails if two test cases for the same user agent and URL expect different results.
+pub fn dedupe_test_cases(test_cases: Vec<TestCaseDefinition>) -> Result<(Vec<TestCaseDefinition>, usize), Box<dyn Error>> {
+    let mut first_seen: HashMap<(String, String), (usize, usize)> = HashMap::new();
+    let mut deduped: Vec<TestCaseDefinition> = Vec::new();
+    let mut removed = 0;
+
+    for (index, test_case) in test_cases.into_iter().enumerate() {
+        let key = (test_case.user_agent.clone(), test_case.url.clone());
+        match first_seen.get(&key) {
+            Some(&(first_index, first)) => {
+                let existing = &deduped[first];
+                if existing.expected_result != test_case.expected_result || existing.expected_crawl_delay != test_case.expected_crawl_delay {
+                    return Err(format!(
+                        "{} and {}: conflicting expectations for {} {}",
+                        describe_position(existing.line_number, first_index),
+                        describe_position(test_case.line_number, index),
+                        test_case.user_agent,
+                        test_case.url
+                    ).into());
+                }
+                removed += 1;
+            },
+            None => {
+                first_seen.insert(key, (index, deduped.len()));
+                deduped.push(test_case);
+            },
+        }
+    }
+    Ok((deduped, removed))
+}
+
+/// Put the test cases in a random order decided by `seed`, the same one for the same seed
+///
+/// Uses SplitMix64 rather than a random number crate, as only the order has to be reproducible.
+pub fn shuffle_test_cases(test_cases: &mut [TestCaseDefinition], seed: u64) {
+    let mut state = seed;
+    let mut next = || {
+        state = state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    };
+    // Fisher-Yates, where the modulo's bias is far too small to matter for an order
+    for index in (1..test_cases.len()).rev() {
+        let other = (next() % (index as u64 + 1)) as usize;
+        test_cases.swap(index, other);
+    }
+}
+
+/// Expand each test case whose user agent is a `|` or `,` separated list, such as
+/// `googlebot|bingbot`, into one test case per user agent
+///
+/// Commas inside parentheses are left alone, as full user agent strings such as
+/// `Mozilla/5.0 (KHTML, like Gecko)` contain them. A test case's name gets the user agent
+/// appended, so the expanded cases can be told apart.
+pub fn expand_user_agents(test_cases: Vec<TestCaseDefinition>) -> Vec<TestCaseDefinition> {
+    let mut expanded = Vec::with_capacity(test_cases.len());
+    for test_case in test_cases {
+        let user_agents = split_user_agents(&test_case.user_agent);
+        if user_agents.len() < 2 {
+            expanded.push(test_case);
+            continue;
+        }
+        for user_agent in user_agents {
+            expanded.push(TestCaseDefinition {
+                user_agent: user_agent.to_string(),
+                name: test_case.name.as_ref().map(|name| format!("{} ({})", name, user_agent)),
+                ..test_case.clone()
+            });
+        }
+    }
+    expanded
+}
+
+fn split_user_agents(user_agents: &str) -> Vec<&str> {
+    let mut split = Vec::new();
+    let mut depth = 0usize;
+    let mut start = 0;
+    for (index, c) in user_agents.char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' => depth = depth.saturating_sub(1),
+            '|' | ',' if depth == 0 || c == '|' => {
+                split.push(user_agents[start..index].trim());
+                start = index + 1;
+            },
+            _ => {},
+        }
+    }
+    split.push(user_agents[start..].trim());
+    split.retain(|user_agent| !user_agent.is_empty());
+    split
+}
+
+/// `line N` when the format tracks lines, `test case N` otherwise
+fn describe_position(line_number: Option<u64>, index: usize) -> String {
+    match line_number {
+        Some(line_number) => format!("line {}", line_number),
+        None => format!("test case {}", index + 1),
+    }
+}
+
+/// Parse an expected result, ignoring case
+///
+/// `allow`, `allowed` and `crawlable` mean true while `disallow`, `disallowed`, `deny`, `denied`
+/// and `blocked` mean false. Anything else falls back to the lenient booleans, such as `true`,
+/// `false`, `yes`, `no`, `1` and `0`.
+fn parse_expected_result(value: &str) -> Option<bool> {
+    match value.trim().to_ascii_lowercase().as_str() {
+        "allow" | "allowed" | "crawlable" => Some(true),
+        "disallow" | "disallowed" | "deny" | "denied" | "blocked" => Some(false),
+        _ => value.parse::<LenientBool>().ok().map(Into::into),
+    }
+}
+
+fn parse_crawl_delay(value: &str) -> Option<f32> {
+    value.parse::<f32>().ok().filter(|seconds| seconds.is_finite() && *seconds >= 0.0)
+}
+
+/// Whether a row only has blank fields, such as `,,`, or starts with a quoted `#` comment
+fn is_blank_or_comment(record: &csv::StringRecord) -> bool {
+    match record.get(0) {
+        Some(first) if first.trim_start().starts_with('#') => true,
+        _ => record.iter().all(|field| field.trim().is_empty()),
+    }
+}
+
+/// The field in `column` along with its index, `None` when the column or field is absent
+fn get_optional_field(record: &csv::StringRecord, column: Option<usize>) -> Option<(usize, &str)> {
+    let column = column?;
+    record.get(column).map(|field| (column, field))
+}
+
+/// CSV content without its blank and `#` comment lines, with the line each remaining line had
+struct CsvLines {
+    content: Vec<u8>,
+    line_numbers: Vec<u64>,
+}
+
+impl CsvLines {
+    /// The line of the original content a record starts on
+    fn line_number(&self, record: &csv::StringRecord) -> u64 {
+        let line = record.position().map_or(0, |position| position.line());
+        usize::try_from(line).ok()
+            .and_then(|line| self.line_numbers.get(line.checked_sub(1)?))
+            .copied()
+            .unwrap_or(line)
+    }
+}
+
+/// Drop blank and `#` comment lines, such as those separating and annotating sections of test
+/// cases, keeping those inside a quoted field
+///
+/// `\r\n`, `\n` and a lone `\r` all end a line.
+fn strip_comment_lines(content: &[u8]) -> CsvLines {
+    let mut lines = CsvLines { content: Vec::with_capacity(content.len()), line_numbers: Vec::new() };
+    let mut in_quotes = false;
+    let mut start = 0;
+    let mut line_number = 0;
+    while start < content.len() {
+        let end = content[start..].iter()
+            .position(|&byte| byte == b'\n' || byte == b'\r')
+            .map_or(content.len(), |offset| start + offset);
+        let next = match content.get(end) {
+            Some(b'\r') if content.get(end + 1) == Some(&b'\n') => end + 2,
+            Some(_) => end + 1,
+            None => end,
+        };
+        line_number += 1;
+
+        let line = &content[start..end];
+        let text = line.trim_ascii_start();
+        if in_quotes || !(text.is_empty() || text.starts_with(b"#")) {
+            lines.content.extend_from_slice(&content[start..next]);
+            lines.line_numbers.push(line_number);
+            // A doubled "" escape leaves the field quoted
+            in_quotes ^= line.iter().filter(|&&byte| byte == b'"').count() % 2 == 1;
+        }
+        start = next;
+    }
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn load_csv(csv: &str, options: &CsvOptions) -> Vec<TestCaseDefinition> {
+        load_test_cases_from_csv_with_options(csv.as_bytes(), options).unwrap()
+    }
+
+    #[test]
+    fn header_columns_may_come_in_any_order() {
+        let test_cases = load_csv("url,expected_result,user_agent\n/private,false,googlebot\n", &CsvOptions::default());
+        assert_eq!(test_cases.len(), 1);
+        assert_eq!(test_cases[0].user_agent, "googlebot");
+        assert_eq!(test_cases[0].url, "/private");
+        assert!(!test_cases[0].expected_result);
+        assert_eq!(test_cases[0].line_number, Some(2));
+    }
+
+    #[test]
+    fn no_header_reads_every_row_positionally() {
+        let options = CsvOptions { has_headers: false, ..CsvOptions::default() };
+        let test_cases = load_csv("googlebot,/private,false\nbingbot,/,true\n", &options);
+        assert_eq!(test_cases.len(), 2);
+        assert_eq!(test_cases[0].user_agent, "googlebot");
+        assert_eq!(test_cases[1].user_agent, "bingbot");
+    }
+
+    #[test]
+    fn unrecognised_first_row_is_a_test_case() {
+        let test_cases = load_csv("googlebot,/private,false\nbingbot,/,true\n", &CsvOptions::default());
+        assert_eq!(test_cases.len(), 2);
+        assert_eq!(test_cases[0].user_agent, "googlebot");
+        assert_eq!(test_cases[0].line_number, Some(1));
+        assert_eq!(test_cases[1].line_number, Some(2));
+    }
+
+    #[test]
+    fn header_without_user_agent_needs_a_default() {
+        let csv = "url,expected_result\n/private,false\n";
+        assert!(load_test_cases_from_csv_with_options(csv.as_bytes(), &CsvOptions::default()).is_err());
+
+        let options = CsvOptions { default_user_agent: Some("googlebot".to_string()), ..CsvOptions::default() };
+        assert_eq!(load_csv(csv, &options)[0].user_agent, "googlebot");
+    }
+
+    #[test]
+    fn semicolon_delimited_rows() {
+        let options = CsvOptions { delimiter: b';', ..CsvOptions::default() };
+        let test_cases = load_csv("user_agent;url;expected_result\ngooglebot;/private;false\n", &options);
+        assert_eq!(test_cases[0].url, "/private");
+        assert!(!test_cases[0].expected_result);
+    }
+
+    #[test]
+    fn quoted_url_may_hold_the_delimiter() {
+        let test_cases = load_csv("user_agent,url,expected_result\ngooglebot,\"/search?q=a,b\",true\n", &CsvOptions::default());
+        assert_eq!(test_cases[0].url, "/search?q=a,b");
+        assert!(test_cases[0].expected_result);
+    }
+
+    #[test]
+    fn tsv_files_are_tab_separated() {
+        let options = CsvOptions::from_path(Path::new("cases.TSV"));
+        assert_eq!(options.delimiter, b'\t');
+        assert_eq!(CsvOptions::from_path(Path::new("cases.csv")).delimiter, b',');
+        assert_eq!(load_csv("user_agent\turl\texpected_result\ngooglebot\t/a,b\tfalse\n", &options)[0].url, "/a,b");
+    }
+
+    #[test]
+    fn expected_result_keywords_and_lenient_booleans() {
+        for value in ["allow", "Allowed", "CRAWLABLE", "true", "yes", "1"] {
+            assert_eq!(parse_expected_result(value), Some(true), "{}", value);
+        }
+        for value in ["disallow", "Disallowed", "deny", "denied", " blocked ", "false", "No", "0"] {
+            assert_eq!(parse_expected_result(value), Some(false), "{}", value);
+        }
+        assert_eq!(parse_expected_result("maybe"), None);
+
+        let test_cases = load_csv("user_agent,url,expected_result\ngooglebot,/a,Disallow\ngooglebot,/b,allow\n", &CsvOptions::default());
+        assert_eq!(test_cases.iter().map(|test| test.expected_result).collect::<Vec<_>>(), [false, true]);
+        let json = r#"[{"user_agent": "googlebot", "url": "/a", "expected_result": "blocked"}]"#;
+        assert!(!load_test_cases_from_json(json.as_bytes()).unwrap()[0].expected_result);
+    }
+
+    #[test]
+    fn every_loader_expands_user_agent_lists() {
+        let user_agents = |test_cases: Vec<TestCaseDefinition>| test_cases.into_iter().map(|test| test.user_agent).collect::<Vec<_>>();
+        let csv = "user_agent,url,expected_result\ngooglebot|bingbot,/private,false\n";
+        assert_eq!(user_agents(load_test_cases_from_csv(csv.as_bytes()).unwrap()), ["googlebot", "bingbot"]);
+        assert_eq!(user_agents(load_test_cases(csv.as_bytes(), TestCaseSource::Csv, &CsvOptions::default()).unwrap()), ["googlebot", "bingbot"]);
+        let json = r#"[{"user_agent": "googlebot, bingbot", "url": "/private", "expected_result": false}]"#;
+        assert_eq!(user_agents(load_test_cases_from_json(json.as_bytes()).unwrap()), ["googlebot", "bingbot"]);
+    }
+
+    #[test]
+    fn comments_and_blank_lines_keep_the_real_line_numbers() {
+        let csv = "# crawlers\nuser_agent,url,expected_result\n\n# private pages\ngooglebot,/private,false\n  # indented\n\r\nbingbot,/,true\n";
+        let test_cases = load_csv(csv, &CsvOptions::default());
+        let lines: Vec<(&str, Option<u64>)> = test_cases.iter().map(|test| (test.user_agent.as_str(), test.line_number)).collect();
+        assert_eq!(lines, [("googlebot", Some(5)), ("bingbot", Some(8))]);
+
+        let csv = "user_agent,url,expected_result\n# bad row next\ngooglebot,/private,maybe\n";
+        let error = load_test_cases_from_csv(csv.as_bytes()).unwrap_err().to_string();
+        assert!(error.starts_with("line 3, column 3:"), "{}", error);
+    }
+
+    #[test]
+    fn comment_lines_inside_quoted_fields_are_kept() {
+        let csv = "user_agent,url,expected_result,name\ngooglebot,/private,false,\"first\n# still the name\"\nbingbot,/,true\n";
+        let test_cases = load_csv(csv, &CsvOptions::default());
+        assert_eq!(test_cases[0].name.as_deref(), Some("first\n# still the name"));
+        assert_eq!(test_cases[1].line_number, Some(4));
+    }
+}
@@ -0,0 +1,249 @@
+mod common;
+
+use std::io::Write;
+
+use flate2::write::GzEncoder;
+use flate2::Compression;
+
+use common::{stderr, stdout, TempDir};
+
+const ROBOTS: &str = "User-agent: googlebot\nAllow: /private\n\nUser-agent: bingbot\nDisallow: /private\n";
+
+#[test]
+fn reads_test_cases_from_stdin() {
+    let dir = TempDir::new();
+    dir.write("robots.txt", ROBOTS);
+
+    let output = dir.run_with_stdin(
+        &["test", "-r", "robots.txt", "-t", "-"],
+        "user_agent,url,expected_result\ngooglebot,/private,true\nbingbot,/private,false\n",
+    );
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(stdout(&output).contains("Test cases run: 2\nPassed tests: 2\nFailed tests: 0\n"), "{}", stdout(&output));
+}
+
+#[test]
+fn reads_robots_txt_from_stdin() {
+    let dir = TempDir::new();
+    dir.write("cases.csv", "user_agent,url,expected_result\nbingbot,/private,true\n");
+
+    let output = dir.run_with_stdin(&["test", "-r", "-", "-t", "cases.csv"], ROBOTS);
+    assert_eq!(output.status.code(), Some(1));
+    assert!(stdout(&output).contains("Passed 0/1 (0.0%)"), "{}", stdout(&output));
+}
+
+#[test]
+fn only_one_input_can_be_stdin() {
+    let output = TempDir::new().run_with_stdin(&["test", "-r", "-", "-t", "-"], "");
+    assert_eq!(output.status.code(), Some(2));
+    assert!(stderr(&output).contains("only one of"), "{}", stderr(&output));
+}
+
+#[test]
+fn delimiter_option_is_used_for_csv_files() {
+    let dir = TempDir::new();
+    dir.write("robots.txt", ROBOTS);
+    dir.write("cases.csv", "user_agent;url;expected_result\nbingbot;\"/private?a=1;b=2\";false\n");
+
+    let output = dir.run(&["test", "-r", "robots.txt", "-t", "cases.csv", "--delimiter", ";"]);
+    assert!(output.status.success(), "{}{}", stdout(&output), stderr(&output));
+}
+
+#[test]
+fn gzipped_robots_txt_gets_the_same_results() {
+    let dir = TempDir::new();
+    dir.write("robots.txt", ROBOTS);
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(ROBOTS.as_bytes()).unwrap();
+    dir.write("robots.txt.gz", encoder.finish().unwrap());
+    dir.write("cases.csv", "user_agent,url,expected_result\ngooglebot,/private,true\nbingbot,/private,true\n");
+
+    let plain = dir.run(&["test", "-r", "robots.txt", "-t", "cases.csv"]);
+    let gzipped = dir.run(&["test", "-r", "robots.txt.gz", "-t", "cases.csv"]);
+    assert_eq!(gzipped.status.code(), Some(1));
+    assert_eq!(test_results(&gzipped), test_results(&plain));
+    assert!(test_results(&gzipped).contains("Passed 1/2"), "{}", stdout(&gzipped));
+}
+
+#[test]
+fn corrupt_gzip_is_a_parse_error() {
+    let dir = TempDir::new();
+    dir.write("robots.txt.gz", "not gzip content");
+    dir.write("cases.csv", "user_agent,url,expected_result\nbingbot,/,true\n");
+
+    let output = dir.run(&["test", "-r", "robots.txt.gz", "-t", "cases.csv"]);
+    assert_eq!(output.status.code(), Some(4), "{}", stderr(&output));
+}
+
+#[test]
+fn byte_order_mark_is_removed_with_a_warning() {
+    let dir = TempDir::new();
+    dir.write("robots.txt", [b"\xef\xbb\xbf".as_slice(), ROBOTS.as_bytes()].concat());
+    dir.write("cases.csv", "user_agent,url,expected_result\ngooglebot,/private,true\nbingbot,/private,false\n");
+
+    let output = dir.run(&["test", "-r", "robots.txt", "-t", "cases.csv"]);
+    assert!(output.status.success(), "{}", stdout(&output));
+    assert!(stderr(&output).contains("robots.txt robots.txt starts with a byte order mark, it was removed"), "{}", stderr(&output));
+}
+
+#[test]
+fn latin1_comment_line_only_warns() {
+    let dir = TempDir::new();
+    dir.write("robots.txt", b"# R\xe9gles du site\nUser-agent: *\nDisallow: /priv\xe9\nDisallow: /private\n");
+    dir.write("cases.csv", "user_agent,url,expected_result\nbingbot,/private,false\nbingbot,/public,true\n");
+
+    let output = dir.run(&["test", "-r", "robots.txt", "-t", "cases.csv"]);
+    assert!(output.status.success(), "{}", stdout(&output));
+    assert!(stderr(&output).contains("robots.txt robots.txt line 1: invalid UTF-8"), "{}", stderr(&output));
+}
+
+#[test]
+fn mixed_case_user_agents_match_but_can_warn() {
+    let dir = TempDir::new();
+    dir.write("robots.txt", ROBOTS);
+    dir.write("cases.csv", "user_agent,url,expected_result\nBingBot,/private,false\n");
+
+    let output = dir.run(&["test", "-r", "robots.txt", "-t", "cases.csv"]);
+    assert!(output.status.success(), "{}", stdout(&output));
+    assert!(!stderr(&output).contains("ignoring case"), "{}", stderr(&output));
+
+    let output = dir.run(&["test", "-r", "robots.txt", "-t", "cases.csv", "--case-insensitive-agent", "false"]);
+    assert!(output.status.success(), "{}", stdout(&output));
+    assert!(stderr(&output).contains("line 4: User-agent \"bingbot\" only matches the test case user agent \"BingBot\" when ignoring case"), "{}", stderr(&output));
+}
+
+#[test]
+fn quiet_hides_every_warning() {
+    let dir = TempDir::new();
+    dir.write("robots.txt", [b"\xef\xbb\xbf".as_slice(), ROBOTS.as_bytes()].concat());
+    dir.write("cases.csv", "user_agent,url,expected_result\nBingBot,/private,false\nduckduckbot,/,true\n");
+
+    let args = ["test", "-r", "robots.txt", "-t", "cases.csv", "--case-insensitive-agent", "false"];
+    let output = dir.run(&args);
+    assert_eq!(stderr(&output).matches("WARN").count(), 3, "{}", stderr(&output));
+
+    let output = dir.run(&[&["-q"], args.as_slice()].concat());
+    assert!(!stderr(&output).contains("WARN"), "{}", stderr(&output));
+}
+
+#[test]
+fn only_failures_leaves_passing_cases_out_of_the_junit_report() {
+    let dir = TempDir::new();
+    dir.write("robots.txt", ROBOTS);
+    dir.write("cases.csv", "user_agent,url,expected_result\ngooglebot,/private,false\ngooglebot,/,true\nbingbot,/private,false\n");
+
+    let output = dir.run(&["test", "-r", "robots.txt", "-t", "cases.csv", "--report-path", "report.xml", "--only-failures"]);
+    assert_eq!(output.status.code(), Some(1));
+    let report = dir.read("report.xml");
+    assert!(report.contains(" tests=\"3\" errors=\"0\" failures=\"1\""), "{}", report);
+    assert_eq!(report.matches("<testcase ").count(), 1, "{}", report);
+    assert!(report.contains("<testcase name=\"Accessing URL: /private as googlebot should be denied\""), "{}", report);
+    // The text summary still counts every test case
+    assert!(stdout(&output).contains("Test cases run: 3\nPassed tests: 2\n"), "{}", stdout(&output));
+}
+
+#[test]
+fn junit_report_is_the_same_whatever_the_test_case_order() {
+    let dir = TempDir::new();
+    dir.write("robots.txt", ROBOTS);
+    let cases: String = ["googlebot", "bingbot", "duckduckbot"].iter()
+        .flat_map(|user_agent| ["/private", "/", "/a", "/b"].map(|url| format!("{},{},true\n", user_agent, url)))
+        .collect();
+    dir.write("cases.csv", format!("user_agent,url,expected_result\n{}", cases));
+
+    for (seed, report) in [("1", "first.xml"), ("2", "second.xml")] {
+        dir.run(&["test", "-r", "robots.txt", "-t", "cases.csv", "--shuffle", seed, "--report-path", report]);
+    }
+    let first = without_timings(&dir.read("first.xml"));
+    assert_eq!(first, without_timings(&dir.read("second.xml")));
+    assert!(first.find("classname=\"bingbot\"") < first.find("classname=\"duckduckbot\""), "{}", first);
+}
+
+#[test]
+fn markdown_and_html_list_test_cases_like_junit() {
+    let dir = TempDir::new();
+    dir.write("robots.txt", ROBOTS);
+    dir.write("cases.csv", "user_agent,url,expected_result\ngooglebot,/a,true\nbingbot,/b,true\ngooglebot,/0,true\n");
+
+    let markdown = stdout(&dir.run(&["test", "-r", "robots.txt", "-t", "cases.csv", "--output-format", "markdown"]));
+    let rows: Vec<&str> = markdown.lines().filter(|line| line.starts_with("| ") && !line.starts_with("| User Agent") && !line.starts_with("| ---")).collect();
+    assert!(rows[0].starts_with("| bingbot | /b "), "{}", markdown);
+    assert!(rows[1].starts_with("| googlebot | /0 "), "{}", markdown);
+    assert!(rows[2].starts_with("| googlebot | /a "), "{}", markdown);
+
+    dir.run(&["test", "-r", "robots.txt", "-t", "cases.csv", "--output-format", "html"]);
+    let html = dir.read("robots-test-results.html");
+    let position = |user_agent_and_url: [&str; 2]| html.find(&format!("<td>{}</td><td>{}</td>", user_agent_and_url[0], user_agent_and_url[1]));
+    let (bingbot, googlebot) = (position(["bingbot", "/b"]).unwrap(), position(["googlebot", "/0"]).unwrap());
+    assert!(bingbot < googlebot, "{}", html);
+}
+
+/// The report without its `time` and `timestamp` attributes, which change on every run
+fn without_timings(report: &str) -> String {
+    let mut output = String::new();
+    let mut rest = report;
+    while let Some(start) = [" time=\"", " timestamp=\""].iter().filter_map(|attribute| rest.find(attribute)).min() {
+        output.push_str(&rest[..start]);
+        let value_start = rest[start..].find('"').unwrap() + start + 1;
+        rest = &rest[value_start + rest[value_start..].find('"').unwrap() + 1..];
+    }
+    output.push_str(rest);
+    output
+}
+
+#[test]
+fn lint_warns_about_mixed_line_endings() {
+    let dir = TempDir::new();
+    dir.write("robots.txt", "User-agent: *\nDisallow: /a\r\nDisallow: /b\n");
+
+    let output = dir.run(&["lint", "-r", "robots.txt"]);
+    assert!(stdout(&output).contains("line 2: mixed line endings, this line ends with \\r\\n while line 1 ends with \\n"), "{}", stdout(&output));
+    assert!(stdout(&output).contains("Lint warnings: 1"), "{}", stdout(&output));
+}
+
+#[test]
+fn explain_as_json() {
+    let dir = TempDir::new();
+    dir.write("robots.txt", "User-agent: bingbot\nCrawl-delay: 5\nDisallow: /private\nAllow: /private/ok\n");
+
+    let output = dir.run(&["explain", "-r", "robots.txt", "--user-agent", "bingbot", "--explain-format", "json", "/private/x"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    let explanation: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(explanation["url"], "/private/x");
+    assert_eq!(explanation["user_agent"], "bingbot");
+    assert_eq!(explanation["allowed"], false);
+    assert_eq!(explanation["matched_rule"], serde_json::json!({"line_number": 3, "key": "Disallow", "value": "/private"}));
+    assert_eq!(explanation["crawl_delay"], 5.0);
+    let rules: Vec<&str> = explanation["applicable_rules"].as_array().unwrap().iter()
+        .map(|rule| rule["value"].as_str().unwrap())
+        .collect();
+    assert_eq!(rules, ["5", "/private", "/private/ok"]);
+
+    let output = dir.run(&["explain", "-r", "robots.txt", "--user-agent", "googlebot", "--explain-format", "json", "/"]);
+    let explanation: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(explanation["allowed"], true);
+    assert!(explanation["matched_rule"].is_null());
+    assert!(explanation["crawl_delay"].is_null());
+    assert_eq!(explanation["applicable_rules"], serde_json::json!([]));
+}
+
+#[test]
+fn github_output_ends_with_the_text_results() {
+    let dir = TempDir::new();
+    dir.write("robots.txt", format!("Host: example.com\n{}", ROBOTS));
+    dir.write("cases.csv", "user_agent,url,expected_result\nbingbot,/private,true\n");
+
+    let args = ["test", "-r", "robots.txt", "-t", "cases.csv", "--show-host", "--by-agent"];
+    let text = dir.run(&args);
+    let github = dir.run(&[args.as_slice(), &["--output-format", "github"]].concat());
+    let github = test_results(&github);
+    let (annotations, rest) = github.split_once('\n').unwrap();
+    assert!(annotations.starts_with("::error file=cases.csv,line=2,"), "{}", annotations);
+    assert_eq!(rest, test_results(&text));
+    assert!(rest.starts_with("Host: example.com\n"), "{}", rest);
+}
+
+/// The output without the line giving the elapsed time
+fn test_results(output: &std::process::Output) -> String {
+    stdout(output).lines().filter(|line| !line.starts_with("Elapsed time")).collect::<Vec<_>>().join("\n")
+}
@@ -0,0 +1,82 @@
+mod common;
+
+use common::{serve, stderr, TempDir};
+
+const TEST_CASES: &str = "user_agent,url,expected_result\nbingbot,/private,false\nbingbot,/public,true\n";
+
+#[test]
+fn fetches_robots_txt_with_the_user_agent_header() {
+    let dir = TempDir::new();
+    dir.write("cases.csv", TEST_CASES);
+    let (url, server) = serve(vec![(200, "User-agent: bingbot\nDisallow: /private\n")]);
+
+    let robots_url = format!("{}/robots.txt", url);
+    let output = dir.run(&["test", "--robots-url", &robots_url, "--user-agent-header", "site-checker/1.0", "-t", "cases.csv"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+
+    let requests = server.join().unwrap();
+    assert!(requests[0].starts_with("GET /robots.txt "), "{}", requests[0]);
+    assert!(requests[0].to_ascii_lowercase().contains("user-agent: site-checker/1.0\r\n"), "{}", requests[0]);
+}
+
+#[test]
+fn client_error_allows_everything() {
+    let dir = TempDir::new();
+    dir.write("cases.csv", "user_agent,url,expected_result\nbingbot,/private,true\n");
+    let (url, server) = serve(vec![(404, "not found")]);
+
+    let output = dir.run(&["test", "--robots-url", &url, "-t", "cases.csv"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(server.join().unwrap().len(), 1);
+}
+
+#[test]
+fn server_error_disallows_everything() {
+    let dir = TempDir::new();
+    dir.write("cases.csv", "user_agent,url,expected_result\nbingbot,/public,false\n");
+    let (url, server) = serve(vec![(503, "unavailable")]);
+
+    let output = dir.run(&["test", "--robots-url", &url, "--retries", "0", "-t", "cases.csv"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    server.join().unwrap();
+}
+
+#[test]
+fn unreachable_server_is_a_network_error() {
+    let dir = TempDir::new();
+    dir.write("cases.csv", TEST_CASES);
+    // Bound and dropped, so nothing listens on the port
+    let url = {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        format!("http://{}/robots.txt", listener.local_addr().unwrap())
+    };
+
+    let output = dir.run(&["test", "--robots-url", &url, "--retries", "0", "-t", "cases.csv"]);
+    assert_eq!(output.status.code(), Some(5));
+    assert!(stderr(&output).contains("error"), "{}", stderr(&output));
+}
+
+#[test]
+fn server_errors_are_retried_until_success() {
+    let dir = TempDir::new();
+    dir.write("cases.csv", TEST_CASES);
+    let (url, server) = serve(vec![(503, "unavailable"), (503, "unavailable"), (200, "User-agent: bingbot\nDisallow: /private\n")]);
+
+    let output = dir.run(&["-v", "test", "--robots-url", &url, "--retries", "2", "--retry-delay", "1", "-t", "cases.csv"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(server.join().unwrap().len(), 3);
+    assert!(stderr(&output).contains("attempt 1 of 3"), "{}", stderr(&output));
+    assert!(stderr(&output).contains("attempt 2 of 3"), "{}", stderr(&output));
+}
+
+#[test]
+fn not_found_is_not_retried() {
+    let dir = TempDir::new();
+    dir.write("cases.csv", "user_agent,url,expected_result\nbingbot,/private,true\n");
+    // A retry would find nothing listening and fail the run
+    let (url, server) = serve(vec![(404, "not found")]);
+
+    let output = dir.run(&["test", "--robots-url", &url, "--retries", "3", "--retry-delay", "1", "-t", "cases.csv"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(server.join().unwrap().len(), 1);
+}